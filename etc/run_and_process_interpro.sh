@@ -12,11 +12,16 @@ fi
 
 INTERPRO_SCAN_VERSION=$1
 
-curl https://curation.pombase.org/dumps/latest_build/fasta/feature_sequences/peptide.fa.gz |
-    gzip -d | perl -pne 's/\*$//' | perl -pne 's/\*/X/g' > pombe_peptide.fa
-
-curl https://www.japonicusdb.org/data/genome_sequence_and_features/feature_sequences/peptide.fa.gz |
-    gzip -d | perl -pne 's/\*$//' | perl -pne 's/\*/X/g' > japonicus_peptide.fa
+# clean-fasta removes trailing stop codons and replaces internal ones with X
+curl -o pombe_peptide_with_stops.fa.gz \
+    https://curation.pombase.org/dumps/latest_build/fasta/feature_sequences/peptide.fa.gz
+/var/pomcur/bin/pombase-domain-process clean-fasta -o pombe_peptide.fa \
+    pombe_peptide_with_stops.fa.gz
+
+curl -o japonicus_peptide_with_stops.fa.gz \
+    https://www.japonicusdb.org/data/genome_sequence_and_features/feature_sequences/peptide.fa.gz
+/var/pomcur/bin/pombase-domain-process clean-fasta -o japonicus_peptide.fa \
+    japonicus_peptide_with_stops.fa.gz
 
 nextflow run ebi-pf-team/interproscan6 -r $INTERPROSCAN_VERSION -profile docker --datadir data --interpro latest --input pombe_peptide.fa --max-workers 1 -c /data/pombase/interproscan6/licensed.conf
 
@@ -0,0 +1,171 @@
+//! Domain architectures: the IDs of the domains of a gene in the order
+//! they occur in the protein, eg. "PF00069~PF00433", and the grouping of
+//! genes that have the same architecture
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::entries::entry_matches;
+use crate::types::{DomainData, GeneMatches};
+
+/// The matches that an architecture is made from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchitectureSource {
+    /// Pfam matches, joined with '~'
+    Pfam,
+    /// InterPro entries, except families and homologous superfamilies,
+    /// joined with '-'
+    InterPro,
+    /// The representative locations of all member databases, joined with '~'
+    Representative,
+}
+
+impl ArchitectureSource {
+    pub fn from_name(name: &str) -> Option<ArchitectureSource> {
+        match name {
+            "pfam" => Some(ArchitectureSource::Pfam),
+            "interpro" => Some(ArchitectureSource::InterPro),
+            "representative" => Some(ArchitectureSource::Representative),
+            _ => None,
+        }
+    }
+
+    fn separator(&self) -> &'static str {
+        match self {
+            ArchitectureSource::InterPro => "-",
+            _ => "~",
+        }
+    }
+}
+
+// Families and homologous superfamilies usually cover the whole protein so
+// they aren't part of the architecture.  Entries of unknown type (eg. from
+// TSV input) are included.
+fn is_architecture_entry_type(interpro_type: Option<&str>) -> bool {
+    !interpro_type.is_some_and(|interpro_type| {
+        interpro_type.eq_ignore_ascii_case("Family") ||
+            interpro_type.eq_ignore_ascii_case("Homologous_superfamily")
+    })
+}
+
+// Return (start, end, ID) for each domain of the gene
+fn gene_domains(gene_matches: &GeneMatches, source: ArchitectureSource)
+    -> Vec<(usize, usize, String)>
+{
+    let mut domains = vec![];
+
+    match source {
+        ArchitectureSource::Pfam => {
+            for interpro_match in &gene_matches.interpro_matches {
+                if !interpro_match.dbname.eq_ignore_ascii_case("Pfam") {
+                    continue;
+                }
+
+                // the fragments of a discontinuous domain are one domain
+                if interpro_match.match_locations.is_empty() {
+                    domains.extend(interpro_match.locations.iter()
+                                   .map(|loc| (loc.start, loc.end, interpro_match.id.clone())));
+                } else {
+                    domains.extend(interpro_match.match_locations.iter()
+                                   .map(|loc| (loc.start, loc.end, interpro_match.id.clone())));
+                }
+            }
+        },
+        ArchitectureSource::InterPro => {
+            for entry_match in entry_matches(&gene_matches.interpro_matches) {
+                if !is_architecture_entry_type(entry_match.interpro_type.as_deref()) {
+                    continue;
+                }
+
+                domains.extend(entry_match.locations.iter()
+                               .map(|loc| (loc.start, loc.end, entry_match.interpro_id.clone())));
+            }
+        },
+        ArchitectureSource::Representative => {
+            for interpro_match in &gene_matches.interpro_matches {
+                domains.extend(interpro_match.match_locations.iter()
+                               .filter(|loc| loc.representative)
+                               .map(|loc| (loc.start, loc.end, interpro_match.id.clone())));
+            }
+        },
+    }
+
+    domains.sort();
+
+    domains
+}
+
+/// Return the architecture of a gene, or None if it has no domains from
+/// the source
+pub fn gene_architecture(gene_matches: &GeneMatches, source: ArchitectureSource)
+    -> Option<String>
+{
+    let domains = gene_domains(gene_matches, source);
+
+    if domains.is_empty() {
+        return None;
+    }
+
+    let ids: Vec<_> = domains.into_iter().map(|(_, _, id)| id).collect();
+
+    Some(ids.join(source.separator()))
+}
+
+/// Return a map from gene ID to architecture for the genes that have one
+pub fn architectures(domain_data: &DomainData, source: ArchitectureSource)
+    -> BTreeMap<String, String>
+{
+    domain_data.domains_by_id.iter()
+        .filter_map(|(gene_uniquename, gene_matches)| {
+            gene_architecture(gene_matches, source)
+                .map(|architecture| (gene_uniquename.clone(), architecture))
+        })
+        .collect()
+}
+
+/// Group the genes by architecture.  Returns a map from architecture to
+/// the sorted gene IDs.
+pub fn group_by_architecture(architectures: &BTreeMap<String, String>)
+    -> BTreeMap<String, Vec<String>>
+{
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (gene_uniquename, architecture) in architectures {
+        groups.entry(architecture.clone()).or_default().push(gene_uniquename.clone());
+    }
+
+    groups
+}
+
+/// Write the architecture of each gene as TSV
+pub fn write_gene_tsv(architectures: &BTreeMap<String, String>, writer: &mut dyn Write)
+    -> io::Result<()>
+{
+    writeln!(writer, "gene_uniquename\tarchitecture")?;
+
+    for (gene_uniquename, architecture) in architectures {
+        writeln!(writer, "{}\t{}", gene_uniquename, architecture)?;
+    }
+
+    Ok(())
+}
+
+/// Write the architecture groups as TSV, largest group first, with the
+/// genes of each group separated by commas
+pub fn write_groups_tsv(groups: &BTreeMap<String, Vec<String>>, writer: &mut dyn Write)
+    -> io::Result<()>
+{
+    let mut groups: Vec<_> = groups.iter().collect();
+    groups.sort_by(|(a_architecture, a_genes), (b_architecture, b_genes)| {
+        b_genes.len().cmp(&a_genes.len()).then_with(|| a_architecture.cmp(b_architecture))
+    });
+
+    writeln!(writer, "architecture\tgene_count\tgenes")?;
+
+    for (architecture, gene_uniquenames) in groups {
+        writeln!(writer, "{}\t{}\t{}", architecture, gene_uniquenames.len(),
+                 gene_uniquenames.join(","))?;
+    }
+
+    Ok(())
+}
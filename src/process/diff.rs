@@ -0,0 +1,375 @@
+//! Compare two sets of processed results, eg. from consecutive releases
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::types::{DomainData, GeneMatches, InterProMatch, Location};
+
+const DBNAME_INTERPRO: &str = "InterPro";
+const DBNAME_TMHMM: &str = "TMHMM";
+const DBNAME_SEGMASKER: &str = "segmasker";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeType {
+    Added,
+    Removed,
+    /// The match is in both files but its locations differ
+    Changed,
+}
+
+impl ChangeType {
+    fn symbol(&self) -> &'static str {
+        match self {
+            ChangeType::Added => "+",
+            ChangeType::Removed => "-",
+            ChangeType::Changed => "~",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ChangeType::Added => "added",
+            ChangeType::Removed => "removed",
+            ChangeType::Changed => "changed",
+        }
+    }
+}
+
+/// A feature of a gene that was added, removed or moved
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeatureChange {
+    pub gene_uniquename: String,
+    /// The member database, "InterPro" for InterPro entries, or "TMHMM" or
+    /// "segmasker"
+    pub dbname: String,
+    /// The match or InterPro entry ID, empty for TMHMM and segmasker
+    pub id: String,
+    pub change: ChangeType,
+    /// One-based locations like "6..60,80..143"
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub old_locations: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub new_locations: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseVersionChange {
+    pub dbname: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+}
+
+/// Change counts for one database
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// The number of genes with a change
+    pub gene_count: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DomainDataDiff {
+    pub old_interproscan_version: String,
+    pub new_interproscan_version: String,
+    pub genes_added: Vec<String>,
+    pub genes_removed: Vec<String>,
+    pub database_versions: Vec<DatabaseVersionChange>,
+    /// Ordered by gene
+    pub changes: Vec<FeatureChange>,
+    pub by_dbname: BTreeMap<String, DatabaseSummary>,
+}
+
+fn format_locations<'a>(locations: impl IntoIterator<Item = &'a Location>) -> String {
+    locations.into_iter()
+        .map(|location| format!("{}..{}", location.start, location.end))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn database_versions(domain_data: &DomainData) -> BTreeMap<&str, &str> {
+    domain_data.domains_by_id.values()
+        .flat_map(|gene_matches| &gene_matches.interpro_matches)
+        .filter_map(|interpro_match| {
+            interpro_match.dbversion.as_deref()
+                .map(|version| (interpro_match.dbname.as_str(), version))
+        })
+        .collect()
+}
+
+fn diff_database_versions(old: &DomainData, new: &DomainData) -> Vec<DatabaseVersionChange> {
+    let old_versions = database_versions(old);
+    let new_versions = database_versions(new);
+
+    let dbnames: BTreeSet<_> = old_versions.keys().chain(new_versions.keys()).collect();
+
+    dbnames.into_iter()
+        .filter_map(|dbname| {
+            let old_version = old_versions.get(dbname);
+            let new_version = new_versions.get(dbname);
+            if old_version == new_version {
+                None
+            } else {
+                Some(DatabaseVersionChange {
+                    dbname: dbname.to_string(),
+                    old_version: old_version.map(|version| version.to_string()),
+                    new_version: new_version.map(|version| version.to_string()),
+                })
+            }
+        })
+        .collect()
+}
+
+// Compare features that are identified only by their location, returning
+// the removed and added locations
+fn diff_location_sets(gene_uniquename: &str, dbname: &str,
+                      old: BTreeSet<Location>, new: BTreeSet<Location>,
+                      changes: &mut Vec<FeatureChange>)
+{
+    let make_change = |location: &Location, change: ChangeType| {
+        let locations = Some(format_locations([location]));
+        let (old_locations, new_locations) =
+            if change == ChangeType::Removed {
+                (locations, None)
+            } else {
+                (None, locations)
+            };
+        FeatureChange {
+            gene_uniquename: gene_uniquename.into(),
+            dbname: dbname.into(),
+            id: String::new(),
+            change,
+            old_locations,
+            new_locations,
+        }
+    };
+
+    changes.extend(old.difference(&new).map(|location| make_change(location, ChangeType::Removed)));
+    changes.extend(new.difference(&old).map(|location| make_change(location, ChangeType::Added)));
+}
+
+// Map from (dbname, match ID) to match
+fn interpro_matches_by_key(gene_matches: Option<&GeneMatches>)
+    -> BTreeMap<(&str, &str), &InterProMatch>
+{
+    gene_matches.into_iter()
+        .flat_map(|gene_matches| &gene_matches.interpro_matches)
+        .map(|interpro_match| ((interpro_match.dbname.as_str(), interpro_match.id.as_str()),
+                               interpro_match))
+        .collect()
+}
+
+fn diff_gene(gene_uniquename: &str, old: Option<&GeneMatches>, new: Option<&GeneMatches>,
+             changes: &mut Vec<FeatureChange>)
+{
+    let old_matches = interpro_matches_by_key(old);
+    let new_matches = interpro_matches_by_key(new);
+
+    let match_keys: BTreeSet<_> = old_matches.keys().chain(new_matches.keys()).collect();
+
+    for key @ (dbname, id) in match_keys {
+        let old_locations = old_matches.get(key)
+            .map(|interpro_match| format_locations(&interpro_match.locations));
+        let new_locations = new_matches.get(key)
+            .map(|interpro_match| format_locations(&interpro_match.locations));
+
+        let change =
+            match (&old_locations, &new_locations) {
+                (Some(old_locations), Some(new_locations)) => {
+                    if old_locations == new_locations {
+                        continue;
+                    }
+                    ChangeType::Changed
+                },
+                (Some(_), None) => ChangeType::Removed,
+                _ => ChangeType::Added,
+            };
+
+        changes.push(FeatureChange {
+            gene_uniquename: gene_uniquename.into(),
+            dbname: dbname.to_string(),
+            id: id.to_string(),
+            change,
+            old_locations,
+            new_locations,
+        });
+    }
+
+    let interpro_ids = |matches: &BTreeMap<_, &InterProMatch>| {
+        matches.values()
+            .filter_map(|interpro_match| interpro_match.interpro_id.clone())
+            .collect::<BTreeSet<_>>()
+    };
+
+    let old_interpro_ids = interpro_ids(&old_matches);
+    let new_interpro_ids = interpro_ids(&new_matches);
+
+    for (interpro_ids, other_interpro_ids, change) in
+        [(&old_interpro_ids, &new_interpro_ids, ChangeType::Removed),
+         (&new_interpro_ids, &old_interpro_ids, ChangeType::Added)]
+    {
+        for interpro_id in interpro_ids.difference(other_interpro_ids) {
+            changes.push(FeatureChange {
+                gene_uniquename: gene_uniquename.into(),
+                dbname: DBNAME_INTERPRO.into(),
+                id: interpro_id.clone(),
+                change,
+                old_locations: None,
+                new_locations: None,
+            });
+        }
+    }
+
+    let tm_locations = |gene_matches: Option<&GeneMatches>| {
+        gene_matches.into_iter()
+            .flat_map(|gene_matches| &gene_matches.tmhmm_matches)
+            .map(|tm_match| Location {
+                start: tm_match.start,
+                end: tm_match.end,
+            })
+            .collect()
+    };
+
+    diff_location_sets(gene_uniquename, DBNAME_TMHMM, tm_locations(old), tm_locations(new),
+                       changes);
+
+    let low_complexity_locations = |gene_matches: Option<&GeneMatches>| {
+        gene_matches.into_iter()
+            .flat_map(|gene_matches| gene_matches.one_based_segmasker_matches())
+            .collect()
+    };
+
+    diff_location_sets(gene_uniquename, DBNAME_SEGMASKER,
+                       low_complexity_locations(old), low_complexity_locations(new),
+                       changes);
+}
+
+/// Compare two sets of results.  Genes that are only in one of the files
+/// have all their features reported as added or removed.
+pub fn diff(old: &DomainData, new: &DomainData) -> DomainDataDiff {
+    let gene_uniquenames: BTreeSet<_> =
+        old.domains_by_id.keys().chain(new.domains_by_id.keys()).collect();
+
+    let mut genes_added = vec![];
+    let mut genes_removed = vec![];
+    let mut changes = vec![];
+
+    for gene_uniquename in gene_uniquenames {
+        let old_gene_matches = old.domains_by_id.get(gene_uniquename);
+        let new_gene_matches = new.domains_by_id.get(gene_uniquename);
+
+        match (old_gene_matches, new_gene_matches) {
+            (None, Some(_)) => genes_added.push(gene_uniquename.clone()),
+            (Some(_), None) => genes_removed.push(gene_uniquename.clone()),
+            _ => (),
+        }
+
+        diff_gene(gene_uniquename, old_gene_matches, new_gene_matches, &mut changes);
+    }
+
+    let mut by_dbname: BTreeMap<String, DatabaseSummary> = BTreeMap::new();
+    let mut genes_by_dbname: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+
+    for change in &changes {
+        let summary = by_dbname.entry(change.dbname.clone()).or_default();
+        match change.change {
+            ChangeType::Added => summary.added += 1,
+            ChangeType::Removed => summary.removed += 1,
+            ChangeType::Changed => summary.changed += 1,
+        }
+        genes_by_dbname.entry(&change.dbname).or_default().insert(&change.gene_uniquename);
+    }
+
+    for (dbname, genes) in genes_by_dbname {
+        if let Some(summary) = by_dbname.get_mut(dbname) {
+            summary.gene_count = genes.len();
+        }
+    }
+
+    DomainDataDiff {
+        old_interproscan_version: old.interproscan_version.clone(),
+        new_interproscan_version: new.interproscan_version.clone(),
+        genes_added,
+        genes_removed,
+        database_versions: diff_database_versions(old, new),
+        changes,
+        by_dbname,
+    }
+}
+
+/// Write a human readable report, with a summary by database followed by
+/// the changes grouped by gene
+pub fn write_text(diff: &DomainDataDiff, writer: &mut dyn Write) -> io::Result<()> {
+    if diff.old_interproscan_version == diff.new_interproscan_version {
+        writeln!(writer, "InterPro version: {}", diff.new_interproscan_version)?;
+    } else {
+        writeln!(writer, "InterPro version: {} -> {}",
+                 diff.old_interproscan_version, diff.new_interproscan_version)?;
+    }
+
+    if !diff.database_versions.is_empty() {
+        writeln!(writer, "\nDatabase versions:")?;
+        for version_change in &diff.database_versions {
+            writeln!(writer, "  {}: {} -> {}", version_change.dbname,
+                     version_change.old_version.as_deref().unwrap_or("none"),
+                     version_change.new_version.as_deref().unwrap_or("none"))?;
+        }
+    }
+
+    writeln!(writer, "\nGenes added: {}", diff.genes_added.len())?;
+    writeln!(writer, "Genes removed: {}", diff.genes_removed.len())?;
+
+    writeln!(writer, "\nBy database:")?;
+    for (dbname, summary) in &diff.by_dbname {
+        writeln!(writer, "  {}: {} added, {} removed, {} changed ({} genes)",
+                 dbname, summary.added, summary.removed, summary.changed,
+                 summary.gene_count)?;
+    }
+
+    writeln!(writer, "\nBy gene:")?;
+    let mut prev_gene = None;
+    for change in &diff.changes {
+        if prev_gene != Some(&change.gene_uniquename) {
+            writeln!(writer, "  {}", change.gene_uniquename)?;
+            prev_gene = Some(&change.gene_uniquename);
+        }
+
+        let mut line = format!("    {} {}", change.change.symbol(), change.dbname);
+        if !change.id.is_empty() {
+            line.push(' ');
+            line.push_str(&change.id);
+        }
+        match (&change.old_locations, &change.new_locations) {
+            (Some(old_locations), Some(new_locations)) =>
+                line.push_str(&format!(" {} -> {}", old_locations, new_locations)),
+            (Some(locations), None) | (None, Some(locations)) =>
+                line.push_str(&format!(" {}", locations)),
+            (None, None) => (),
+        }
+        writeln!(writer, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+/// Write the changes as TSV with one line per changed feature
+pub fn write_tsv(diff: &DomainDataDiff, writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(writer, "gene_uniquename\tdbname\tid\tchange\told_locations\tnew_locations")?;
+
+    for change in &diff.changes {
+        writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}",
+                 change.gene_uniquename, change.dbname, change.id, change.change.name(),
+                 change.old_locations.as_deref().unwrap_or(""),
+                 change.new_locations.as_deref().unwrap_or(""))?;
+    }
+
+    Ok(())
+}
+
+/// Write the whole diff, including the per-database summary, as JSON
+pub fn write_json(diff: &DomainDataDiff, writer: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, diff)?;
+    writeln!(writer)
+}
@@ -0,0 +1,75 @@
+//! InterPro entry level features.  A gene often has matches to several
+//! member database signatures (eg. Pfam, SMART and PROSITE) that are
+//! integrated into the same InterPro entry.  These are combined into one
+//! feature per entry.
+
+use std::collections::BTreeMap;
+
+use crate::types::{DomainData, EntrySignature, InterProEntryMatch, InterProMatch};
+use crate::util::merge_locations;
+
+/// Combine the matches by InterPro entry.  The locations of each entry are
+/// the union of the locations of its signatures, with overlapping and
+/// abutting locations merged.  Matches that aren't integrated into an
+/// entry are ignored.  The result is sorted by start position.
+pub fn entry_matches(interpro_matches: &[InterProMatch]) -> Vec<InterProEntryMatch> {
+    let mut entries: BTreeMap<&str, InterProEntryMatch> = BTreeMap::new();
+
+    for interpro_match in interpro_matches {
+        let Some(ref interpro_id) = interpro_match.interpro_id
+        else {
+            continue;
+        };
+
+        let entry = entries.entry(interpro_id)
+            .or_insert_with(|| InterProEntryMatch {
+                interpro_id: interpro_id.clone(),
+                interpro_name: interpro_match.interpro_name.clone(),
+                interpro_description: interpro_match.interpro_description.clone(),
+                interpro_type: interpro_match.interpro_type.clone(),
+                signatures: vec![],
+                match_start: usize::MAX,
+                match_end: 0,
+                locations: vec![],
+            });
+
+        let signature = EntrySignature {
+            dbname: interpro_match.dbname.clone(),
+            id: interpro_match.id.clone(),
+        };
+        if !entry.signatures.contains(&signature) {
+            entry.signatures.push(signature);
+        }
+
+        entry.locations.extend(interpro_match.locations.iter().cloned());
+    }
+
+    let mut entry_matches: Vec<_> = entries.into_values()
+        .map(|mut entry| {
+            entry.signatures.sort();
+            entry.locations.sort();
+            merge_locations(&mut entry.locations);
+            for location in &entry.locations {
+                entry.match_start = entry.match_start.min(location.start);
+                entry.match_end = entry.match_end.max(location.end);
+            }
+            entry
+        })
+        .collect();
+
+    entry_matches.sort_by(|a, b| {
+        a.match_start.cmp(&b.match_start).then_with(|| a.interpro_id.cmp(&b.interpro_id))
+    });
+
+    entry_matches
+}
+
+/// Set the `interpro_entries` of every gene from its `interpro_matches`.
+/// This needs to be called after the score and E-value filtering but
+/// before filter::representative_only() so that the entries include all of
+/// the signatures.
+pub fn add_entry_matches(domain_data: &mut DomainData) {
+    for gene_matches in domain_data.domains_by_id.values_mut() {
+        gene_matches.interpro_entries = entry_matches(&gene_matches.interpro_matches);
+    }
+}
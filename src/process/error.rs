@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors returned by the functions of this crate.  Where possible the file
+/// name and line number of the problem are included.
+#[derive(Debug)]
+pub enum DomainProcessError {
+    /// Failed to open, read or write a file
+    Io {
+        filename: String,
+        source: io::Error,
+    },
+    /// A JSON file couldn't be parsed or written
+    Json {
+        filename: String,
+        source: serde_json::Error,
+    },
+    /// An XML file couldn't be parsed
+    Xml {
+        filename: String,
+        source: roxmltree::Error,
+    },
+    /// A TSV file couldn't be parsed
+    Tsv {
+        filename: String,
+        source: csv::Error,
+    },
+    /// A file was readable but its contents weren't in the expected form
+    MalformedInput {
+        filename: String,
+        line_number: Option<usize>,
+        message: String,
+    },
+    /// A line of segmasker output couldn't be parsed
+    MalformedSegmaskerLine {
+        line_number: usize,
+        line: String,
+    },
+    /// The regex for converting protein IDs to gene IDs is invalid
+    InvalidIdPattern {
+        pattern: String,
+        message: String,
+    },
+    /// An external program (eg. TMHMM) couldn't be run or failed
+    ToolFailure {
+        tool: String,
+        message: String,
+    },
+    /// The InterProScan results don't match the peptide FASTA file
+    SequenceMismatch {
+        changed_count: usize,
+        missing_count: usize,
+        not_in_fasta_count: usize,
+    },
+    /// Only representative domains were requested but the InterProScan
+    /// results don't mark any
+    NoRepresentativeLocations,
+    /// Too many genes have features outside their protein sequence
+    OutOfRangeFeatures {
+        gene_count: usize,
+        max_gene_count: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, DomainProcessError>;
+
+impl DomainProcessError {
+    pub fn io(filename: &str, source: io::Error) -> DomainProcessError {
+        DomainProcessError::Io {
+            filename: filename.into(),
+            source,
+        }
+    }
+
+    pub fn malformed(filename: &str, line_number: Option<usize>, message: impl Into<String>)
+        -> DomainProcessError
+    {
+        DomainProcessError::MalformedInput {
+            filename: filename.into(),
+            line_number,
+            message: message.into(),
+        }
+    }
+
+    pub fn tool_failure(tool: &str, message: impl Into<String>) -> DomainProcessError {
+        DomainProcessError::ToolFailure {
+            tool: tool.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for DomainProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DomainProcessError::Io { filename, source } =>
+                write!(f, "failed to access {}: {}", filename, source),
+            DomainProcessError::Json { filename, source } =>
+                write!(f, "JSON error in {}: {}", filename, source),
+            DomainProcessError::Xml { filename, source } =>
+                write!(f, "failed to parse {}: {}", filename, source),
+            DomainProcessError::Tsv { filename, source } =>
+                write!(f, "failed to parse {}: {}", filename, source),
+            DomainProcessError::MalformedInput { filename, line_number: Some(line_number), message } =>
+                write!(f, "{} line {}: {}", filename, line_number, message),
+            DomainProcessError::MalformedInput { filename, line_number: None, message } =>
+                write!(f, "{}: {}", filename, message),
+            DomainProcessError::MalformedSegmaskerLine { line_number, line } =>
+                write!(f, "can't parse line {} of segmasker output: {}", line_number, line),
+            DomainProcessError::InvalidIdPattern { pattern, message } =>
+                write!(f, "invalid ID pattern \"{}\": {}", pattern, message),
+            DomainProcessError::ToolFailure { tool, message } =>
+                write!(f, "failed to run {}: {}", tool, message),
+            DomainProcessError::SequenceMismatch { changed_count, missing_count,
+                                                   not_in_fasta_count } =>
+                write!(f, "InterProScan results don't match the protein sequences: \
+                           {} changed, {} missing from the results, {} not in the FASTA file",
+                       changed_count, missing_count, not_in_fasta_count),
+            DomainProcessError::NoRepresentativeLocations =>
+                write!(f, "the InterProScan results have no representative domains \
+                           (they need InterProScan 5.60 or later JSON or XML output)"),
+            DomainProcessError::OutOfRangeFeatures { gene_count, max_gene_count } =>
+                write!(f, "{} genes have features outside their protein sequence \
+                           (maximum allowed: {})", gene_count, max_gene_count),
+        }
+    }
+}
+
+impl Error for DomainProcessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DomainProcessError::Io { source, .. } => Some(source),
+            DomainProcessError::Json { source, .. } => Some(source),
+            DomainProcessError::Xml { source, .. } => Some(source),
+            DomainProcessError::Tsv { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
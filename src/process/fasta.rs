@@ -0,0 +1,113 @@
+use std::io::{self, BufRead, Write};
+
+use crate::error::{DomainProcessError, Result};
+use crate::util::open_input;
+
+/// A protein from a peptide FASTA file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Protein {
+    /// The first word of the header line
+    pub id: String,
+    /// The rest of the header line, if any
+    pub description: Option<String>,
+    pub sequence: String,
+}
+
+impl Protein {
+    pub fn len(&self) -> usize {
+        self.sequence.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequence.is_empty()
+    }
+
+    /// The lower case hex MD5 of the sequence, as used by InterProScan
+    pub fn md5(&self) -> String {
+        format!("{:x}", md5::compute(self.sequence.as_bytes()))
+    }
+}
+
+// Remove the trailing stop codon and replace internal stops (eg. from
+// selenocysteine codons) with X, which InterProScan and TMHMM don't accept
+fn clean_sequence(sequence: &mut String) {
+    while sequence.ends_with('*') {
+        sequence.pop();
+    }
+
+    if sequence.contains('*') {
+        *sequence = sequence.replace('*', "X");
+    }
+}
+
+/// Parse a peptide FASTA file.  Stop codons ('*') are removed from the end
+/// of the sequences and internal stops are replaced with 'X'.  The
+/// `source_name` is used in error messages.
+pub fn parse(reader: &mut dyn BufRead, source_name: &str) -> Result<Vec<Protein>> {
+    let mut proteins: Vec<Protein> = vec![];
+
+    for (idx, line_result) in reader.lines().enumerate() {
+        let line = line_result.map_err(|err| DomainProcessError::io(source_name, err))?;
+        let line = line.trim_end();
+
+        if let Some(header) = line.strip_prefix('>') {
+            let (id, description) = match header.split_once(char::is_whitespace) {
+                Some((id, description)) => {
+                    let description = description.trim();
+                    (id, if description.is_empty() { None } else { Some(description) })
+                },
+                None => (header, None),
+            };
+
+            if id.is_empty() {
+                return Err(DomainProcessError::malformed(source_name, Some(idx + 1),
+                                                         "FASTA header without an ID"));
+            }
+
+            proteins.push(Protein {
+                id: id.to_owned(),
+                description: description.map(|description| description.to_owned()),
+                sequence: String::new(),
+            });
+        } else if !line.is_empty() {
+            match proteins.last_mut() {
+                Some(protein) => protein.sequence.push_str(line.trim_start()),
+                None => {
+                    return Err(DomainProcessError::malformed(source_name, Some(idx + 1),
+                                                             "sequence before the first header"));
+                }
+            }
+        }
+    }
+
+    for protein in proteins.iter_mut() {
+        clean_sequence(&mut protein.sequence);
+    }
+
+    Ok(proteins)
+}
+
+/// Read a (possibly compressed) peptide FASTA file
+pub fn read(filename: &str) -> Result<Vec<Protein>> {
+    let mut reader = open_input(filename)?;
+    parse(&mut reader, filename)
+}
+
+/// Write proteins in FASTA format with 60 residues per line
+pub fn write<'a>(proteins: impl IntoIterator<Item = &'a Protein>, writer: &mut dyn Write)
+    -> io::Result<()>
+{
+    for protein in proteins {
+        match protein.description {
+            Some(ref description) => writeln!(writer, ">{} {}", protein.id, description)?,
+            None => writeln!(writer, ">{}", protein.id)?,
+        }
+
+        for chunk in protein.sequence.as_bytes().chunks(60) {
+            writer.write_all(chunk)?;
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
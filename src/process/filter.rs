@@ -0,0 +1,171 @@
+//! Removal of weak member database matches using per-database score and
+//! E-value thresholds, and of the locations that aren't in InterProScan's
+//! representative set of domains
+
+use std::collections::HashMap;
+
+use crate::types::{DomainData, InterProMatch, Location};
+use crate::util::merge_locations;
+
+/// Score and E-value cut-offs keyed by lower case database name, eg. "pfam"
+#[derive(Debug, Clone, Default)]
+pub struct MatchThresholds {
+    max_evalue: HashMap<String, f64>,
+    min_score: HashMap<String, f64>,
+}
+
+/// Parse a "DBNAME=VALUE" threshold, eg. "Pfam=1e-5"
+pub fn parse_threshold(spec: &str) -> Option<(String, f64)> {
+    let (dbname, value) = spec.split_once('=')?;
+    let dbname = dbname.trim();
+    let value = value.trim().parse::<f64>().ok()?;
+
+    if dbname.is_empty() || value.is_nan() {
+        return None;
+    }
+
+    Some((dbname.to_owned(), value))
+}
+
+impl MatchThresholds {
+    pub fn set_max_evalue(&mut self, dbname: &str, max_evalue: f64) {
+        self.max_evalue.insert(dbname.to_ascii_lowercase(), max_evalue);
+    }
+
+    pub fn set_min_score(&mut self, dbname: &str, min_score: f64) {
+        self.min_score.insert(dbname.to_ascii_lowercase(), min_score);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_evalue.is_empty() && self.min_score.is_empty()
+    }
+
+    /// Return true if the match passes the thresholds for its database.
+    /// The match E-value and score are used if InterProScan reported them,
+    /// otherwise the best value of the locations.  Matches without a value
+    /// (eg. from TSV input) always pass.
+    pub fn passes(&self, interpro_match: &InterProMatch) -> bool {
+        let dbname = interpro_match.dbname.to_ascii_lowercase();
+
+        if let Some(&max_evalue) = self.max_evalue.get(&dbname) {
+            let evalue = interpro_match.evalue.or_else(|| {
+                interpro_match.match_locations.iter()
+                    .filter_map(|location| location.evalue)
+                    .min_by(f64::total_cmp)
+            });
+            if evalue.is_some_and(|evalue| evalue > max_evalue) {
+                return false;
+            }
+        }
+
+        if let Some(&min_score) = self.min_score.get(&dbname) {
+            let score = interpro_match.score.or_else(|| {
+                interpro_match.match_locations.iter()
+                    .filter_map(|location| location.score)
+                    .max_by(f64::total_cmp)
+            });
+            if score.is_some_and(|score| score < min_score) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Remove the matches that fail the thresholds.  Returns the number of
+    /// matches removed.
+    pub fn apply(&self, domain_data: &mut DomainData) -> usize {
+        let mut removed_count = 0;
+
+        for gene_matches in domain_data.domains_by_id.values_mut() {
+            let match_count = gene_matches.interpro_matches.len();
+            gene_matches.interpro_matches
+                .retain(|interpro_match| self.passes(interpro_match));
+            removed_count += match_count - gene_matches.interpro_matches.len();
+        }
+
+        removed_count
+    }
+}
+
+// The member databases that InterProScan chooses representative domains
+// and families from.  Other analyses (eg. MobiDB-lite, COILS, Phobius and
+// SignalP) never have representative locations.
+const REPRESENTATIVE_DBNAMES: &[&str] = &[
+    "CDD", "HAMAP", "NCBIfam", "PANTHER", "Pfam", "PIRSF", "PROSITE profiles", "SFLD", "SMART",
+];
+
+/// Return true if InterProScan can mark the locations of matches from the
+/// database as representative
+pub fn can_be_representative(dbname: &str) -> bool {
+    REPRESENTATIVE_DBNAMES.iter()
+        .any(|representative_dbname| representative_dbname.eq_ignore_ascii_case(dbname))
+}
+
+/// Return true if any match location is marked as representative.  Results
+/// from TSV input and from InterProScan versions before 5.60 have no
+/// representative locations.
+pub fn has_representative_locations(domain_data: &DomainData) -> bool {
+    domain_data.domains_by_id.values()
+        .flat_map(|gene_matches| &gene_matches.interpro_matches)
+        .flat_map(|interpro_match| &interpro_match.match_locations)
+        .any(|match_location| match_location.representative)
+}
+
+/// Reduce the matches of the databases that InterProScan chooses
+/// representative domains from to its non-overlapping set of domains for
+/// each protein: only the representative locations are kept and matches
+/// without a representative location are removed.  The flat `locations`,
+/// `match_start` and `match_end` are recalculated from the remaining
+/// locations.  Matches from other analyses (eg. MobiDB-lite disorder) are
+/// unchanged.  Returns the number of matches removed.
+pub fn representative_only(domain_data: &mut DomainData) -> usize {
+    let mut removed_count = 0;
+
+    for gene_matches in domain_data.domains_by_id.values_mut() {
+        let match_count = gene_matches.interpro_matches.len();
+
+        gene_matches.interpro_matches.retain_mut(|interpro_match| {
+            if !can_be_representative(&interpro_match.dbname) {
+                return true;
+            }
+
+            interpro_match.match_locations
+                .retain(|match_location| match_location.representative);
+
+            if interpro_match.match_locations.is_empty() {
+                return false;
+            }
+
+            let mut locations = vec![];
+
+            for match_location in &interpro_match.match_locations {
+                if match_location.fragments.is_empty() {
+                    locations.push(Location {
+                        start: match_location.start,
+                        end: match_location.end,
+                    });
+                } else {
+                    locations.extend(match_location.fragments.iter()
+                                     .map(|fragment| Location {
+                                         start: fragment.start,
+                                         end: fragment.end,
+                                     }));
+                }
+            }
+
+            locations.sort();
+            merge_locations(&mut locations);
+
+            interpro_match.match_start = locations.iter().map(|loc| loc.start).min().unwrap_or(0);
+            interpro_match.match_end = locations.iter().map(|loc| loc.end).max().unwrap_or(0);
+            interpro_match.locations = locations;
+
+            true
+        });
+
+        removed_count += match_count - gene_matches.interpro_matches.len();
+    }
+
+    removed_count
+}
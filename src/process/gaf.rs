@@ -0,0 +1,169 @@
+//! GO annotation from the InterPro2GO mappings of the InterPro entries
+//! matched by each gene, in GAF 2.2 format
+
+use std::collections::{BTreeSet, HashSet};
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{DomainProcessError, Result};
+use crate::types::DomainData;
+use crate::util::{open_input, OutputWriter};
+
+const EVIDENCE_CODE: &str = "IEA";
+const REFERENCE: &str = "GO_REF:0000002";
+const ASSIGNED_BY: &str = "InterPro";
+
+// "protein-containing complex"
+const PROTEIN_CONTAINING_COMPLEX: &str = "GO:0032991";
+
+/// Settings for the columns that don't come from the InterPro matches
+#[derive(Debug, Clone)]
+pub struct GafOptions {
+    /// The database of the gene IDs, eg. "PomBase"
+    pub db: String,
+    /// The NCBI taxon ID, eg. 4896
+    pub taxon: u32,
+    /// The annotation date as YYYYMMDD
+    pub date: String,
+    /// GO terms that shouldn't be annotated
+    pub blacklist: HashSet<String>,
+    /// The descendants of "protein-containing complex" (GO:0032991), which
+    /// are annotated with "part_of" instead of "located_in"
+    pub complex_terms: HashSet<String>,
+}
+
+/// Return today's date (UTC) as YYYYMMDD
+pub fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+
+    // civil_from_days() from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+/// Read a file of GO IDs, one per line, eg. the terms to exclude or the
+/// protein-containing complex terms.  Blank lines, lines starting with '#'
+/// and anything after the first word are ignored.
+pub fn read_go_ids(filename: &str) -> Result<HashSet<String>> {
+    let reader = open_input(filename)?;
+
+    let mut go_ids = HashSet::new();
+
+    for line_result in reader.lines() {
+        let line = line_result.map_err(|err| DomainProcessError::io(filename, err))?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(go_id) = line.split_whitespace().next() {
+            go_ids.insert(go_id.to_owned());
+        }
+    }
+
+    Ok(go_ids)
+}
+
+// GAF 2.2 requires a gene product to GO term relation in the qualifier
+// column.  Annotations to protein-containing complexes use "part_of" and
+// other cellular components use "located_in".
+fn aspect_and_relation(go_id: &str, category: &str, complex_terms: &HashSet<String>)
+    -> Option<(&'static str, &'static str)>
+{
+    match category {
+        "MOLECULAR_FUNCTION" => Some(("F", "enables")),
+        "BIOLOGICAL_PROCESS" => Some(("P", "involved_in")),
+        "CELLULAR_COMPONENT" => {
+            if go_id == PROTEIN_CONTAINING_COMPLEX || complex_terms.contains(go_id) {
+                Some(("C", "part_of"))
+            } else {
+                Some(("C", "located_in"))
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Write a GAF 2.2 line for each distinct gene, GO term and InterPro entry.
+/// GO xrefs without a category (eg. from TSV input) are skipped because
+/// the aspect isn't known.  Cellular component terms get "part_of" only if
+/// they are GO:0032991 or in `options.complex_terms`, so without the list
+/// of complex terms those annotations need their relation fixed later.  Returns the number of annotations written.
+pub fn write_gaf(domain_data: &DomainData, options: &GafOptions, writer: &mut dyn Write)
+    -> io::Result<usize>
+{
+    writeln!(writer, "!gaf-version: 2.2")?;
+    writeln!(writer, "!generated-by: {}", env!("CARGO_PKG_NAME"))?;
+    let date = &options.date;
+    if date.len() == 8 && date.is_ascii() {
+        writeln!(writer, "!date-generated: {}-{}-{}", &date[0..4], &date[4..6], &date[6..8])?;
+    }
+    writeln!(writer, "!InterPro version: {}", domain_data.interproscan_version)?;
+
+    let mut annotation_count = 0;
+
+    for (gene_uniquename, gene_matches) in &domain_data.domains_by_id {
+        // (GO ID, InterPro ID, aspect, relation), sorted and without duplicates
+        let mut annotations = BTreeSet::new();
+
+        for interpro_match in &gene_matches.interpro_matches {
+            let Some(ref interpro_id) = interpro_match.interpro_id
+            else {
+                continue;
+            };
+
+            for go_xref in &interpro_match.go_xrefs {
+                if options.blacklist.contains(&go_xref.id) {
+                    continue;
+                }
+
+                let Some((aspect, relation)) =
+                    go_xref.category.as_deref().and_then(|category| {
+                        aspect_and_relation(&go_xref.id, category, &options.complex_terms)
+                    })
+                else {
+                    continue;
+                };
+
+                annotations.insert((&go_xref.id, interpro_id, aspect, relation));
+            }
+        }
+
+        for (go_id, interpro_id, aspect, relation) in annotations {
+            writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}\t{}\tInterPro:{}\t{}\t\t\tprotein\t\
+                              taxon:{}\t{}\t{}\t\t",
+                     options.db, gene_uniquename, gene_uniquename, relation, go_id,
+                     REFERENCE, EVIDENCE_CODE, interpro_id, aspect, options.taxon,
+                     options.date, ASSIGNED_BY)?;
+            annotation_count += 1;
+        }
+    }
+
+    Ok(annotation_count)
+}
+
+/// Write a GAF file, compressing if the file name ends with ".gz" or ".zst"
+pub fn write_gaf_file(domain_data: &DomainData, options: &GafOptions, filename: &str)
+    -> Result<usize>
+{
+    let mut writer = OutputWriter::create(filename)?;
+
+    let annotation_count = write_gaf(domain_data, options, &mut writer)
+        .map_err(|err| DomainProcessError::io(filename, err))?;
+
+    writer.finish()?;
+
+    Ok(annotation_count)
+}
@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+
+use crate::error::{DomainProcessError, Result};
+use crate::types::{DomainData, GeneMatches};
+use crate::util::OutputWriter;
+
+const SOURCE_TMHMM: &str = "TMHMM";
+// the source of low complexity regions from results files that don't
+// record the method
+const SOURCE_SEGMASKER: &str = "segmasker";
+
+// Sequence Ontology terms for the feature type column
+const TYPE_PROTEIN_MATCH: &str = "protein_match";
+const TYPE_TRANSMEMBRANE: &str = "transmembrane_polypeptide_region";
+const TYPE_LOW_COMPLEXITY: &str = "compositionally_biased_region_of_peptide";
+
+// Escape the characters that have a meaning in GFF3 columns and attributes
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\t' | '\n' | '\r' | '%' | ';' | '=' | '&' | ',' => {
+                escaped.push_str(&format!("%{:02X}", c as u32));
+            },
+            c if c.is_control() => {
+                escaped.push_str(&format!("%{:02X}", c as u32));
+            },
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn write_line(writer: &mut dyn Write, seqid: &str, source: &str, feature_type: &str,
+              start: usize, end: usize, attributes: &[(&str, String)])
+    -> io::Result<()>
+{
+    let attributes_string =
+        if attributes.is_empty() {
+            ".".into()
+        } else {
+            attributes.iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+
+    writeln!(writer, "{}\t{}\t{}\t{}\t{}\t.\t.\t.\t{}",
+             escape(seqid), escape(source), feature_type, start, end, attributes_string)
+}
+
+fn write_gene_features(writer: &mut dyn Write, gene_matches: &GeneMatches,
+                       low_complexity_source: &str)
+    -> io::Result<()>
+{
+    let seqid = &gene_matches.gene_uniquename;
+
+    for interpro_match in &gene_matches.interpro_matches {
+        let mut attributes = vec![
+            ("ID", escape(&format!("{}:{}", seqid, interpro_match.id))),
+            ("Name", escape(&interpro_match.id)),
+        ];
+
+        let note = interpro_match.description.as_ref()
+            .or(interpro_match.name.as_ref());
+        if let Some(note) = note {
+            attributes.push(("Note", escape(note)));
+        }
+
+        if let Some(ref interpro_id) = interpro_match.interpro_id {
+            attributes.push(("Dbxref", format!("InterPro:{}", escape(interpro_id))));
+        }
+
+        if !interpro_match.go_xrefs.is_empty() {
+            let go_ids: Vec<_> = interpro_match.go_xrefs.iter()
+                .map(|go_xref| escape(&go_xref.id))
+                .collect();
+            attributes.push(("Ontology_term", go_ids.join(",")));
+        }
+
+        // a match with several locations is written as a discontinuous
+        // feature: one line per location, all with the same ID
+        for location in &interpro_match.locations {
+            write_line(writer, seqid, &interpro_match.dbname, TYPE_PROTEIN_MATCH,
+                       location.start, location.end, &attributes)?;
+        }
+    }
+
+    for tm_match in &gene_matches.tmhmm_matches {
+        write_line(writer, seqid, SOURCE_TMHMM, TYPE_TRANSMEMBRANE,
+                   tm_match.start, tm_match.end, &[])?;
+    }
+
+    for location in gene_matches.one_based_segmasker_matches() {
+        write_line(writer, seqid, low_complexity_source, TYPE_LOW_COMPLEXITY,
+                   location.start, location.end, &[])?;
+    }
+
+    Ok(())
+}
+
+/// Write the InterPro, TMHMM and low complexity features in GFF3 format
+/// using protein coordinates.  The gene ID is used as the sequence ID.
+pub fn write_gff3(domain_data: &DomainData, writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(writer, "##gff-version 3")?;
+    writeln!(writer, "# InterPro {}", domain_data.interproscan_version)?;
+
+    let low_complexity_source = domain_data.low_complexity_method.as_deref()
+        .unwrap_or(SOURCE_SEGMASKER);
+
+    for gene_matches in domain_data.domains_by_id.values() {
+        write_gene_features(writer, gene_matches, low_complexity_source)?;
+    }
+
+    Ok(())
+}
+
+/// Write the features to a GFF3 file, compressing if the file name ends
+/// with ".gz" or ".zst".
+pub fn write_gff3_file(domain_data: &DomainData, filename: &str) -> Result<()> {
+    let mut writer = OutputWriter::create(filename)?;
+
+    write_gff3(domain_data, &mut writer)
+        .map_err(|err| DomainProcessError::io(filename, err))?;
+
+    writer.finish()
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use regex::Regex;
+
+use crate::error::{DomainProcessError, Result};
+use crate::util::open_input;
+
+/// The default ID pattern, which removes the ".1:pep" suffix that PomBase
+/// and JaponicusDB add to peptide IDs
+pub const DEFAULT_ID_PATTERN: &str = r"^(\S+?)(?:\.1:pep)?$";
+
+/// Converts the protein IDs found in the InterProScan results, FASTA headers
+/// and TMHMM/segmasker output to gene IDs.  IDs that don't match the regex
+/// or aren't in the mapping file are returned unchanged.
+#[derive(Debug, Clone)]
+pub enum IdMapper {
+    /// The gene ID is the first capture group of the regex
+    Pattern(Regex),
+    /// A map from protein ID to gene ID
+    Mapping(HashMap<String, String>),
+}
+
+impl Default for IdMapper {
+    fn default() -> IdMapper {
+        IdMapper::Pattern(Regex::new(DEFAULT_ID_PATTERN).unwrap())
+    }
+}
+
+impl IdMapper {
+    /// Create a mapper from a regex with at least one capture group
+    pub fn from_pattern(pattern: &str) -> Result<IdMapper> {
+        let regex = Regex::new(pattern)
+            .map_err(|err| DomainProcessError::InvalidIdPattern {
+                pattern: pattern.into(),
+                message: err.to_string(),
+            })?;
+
+        if regex.captures_len() < 2 {
+            return Err(DomainProcessError::InvalidIdPattern {
+                pattern: pattern.into(),
+                message: "the pattern needs a capture group for the gene ID".into(),
+            });
+        }
+
+        Ok(IdMapper::Pattern(regex))
+    }
+
+    /// Read a file with two whitespace separated columns: protein ID and
+    /// gene ID.  Blank lines and lines starting with '#' are ignored.
+    pub fn from_mapping_file(filename: &str) -> Result<IdMapper> {
+        let reader = open_input(filename)?;
+
+        let mut mapping = HashMap::new();
+
+        for (idx, line_result) in reader.lines().enumerate() {
+            let line = line_result.map_err(|err| DomainProcessError::io(filename, err))?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(protein_id), Some(gene_id), None) => {
+                    mapping.insert(protein_id.to_owned(), gene_id.to_owned());
+                },
+                _ => {
+                    return Err(DomainProcessError::malformed(filename, Some(idx + 1),
+                                                             "expected two columns"));
+                }
+            }
+        }
+
+        Ok(IdMapper::Mapping(mapping))
+    }
+
+    /// Return the gene ID for a protein ID
+    pub fn gene_id(&self, protein_id: &str) -> String {
+        match self {
+            IdMapper::Pattern(regex) => {
+                regex.captures(protein_id)
+                    .and_then(|captures| captures.get(1))
+                    .map(|gene_id| gene_id.as_str().to_owned())
+                    .unwrap_or_else(|| protein_id.to_owned())
+            },
+            IdMapper::Mapping(mapping) => {
+                mapping.get(protein_id)
+                    .cloned()
+                    .unwrap_or_else(|| protein_id.to_owned())
+            },
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Support for re-running InterProScan on only the proteins that have
+//! changed since a previous run.  The sequence MD5s stored in the previous
+//! output are compared with the new FASTA file: results for unchanged
+//! proteins are reused and only new and changed proteins need to be given
+//! to InterProScan.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::fasta::Protein;
+use crate::id_mapping::IdMapper;
+use crate::types::{DomainData, GeneMatches};
+
+// Map from gene ID to the MD5 of its protein in the previous results
+fn previous_md5s(previous: &DomainData) -> HashMap<&str, &str> {
+    previous.domains_by_id.iter()
+        .filter_map(|(gene_uniquename, gene_matches)| {
+            gene_matches.sequence_md5.as_deref()
+                .map(|md5| (gene_uniquename.as_str(), md5))
+        })
+        .collect()
+}
+
+/// Return the proteins that are new or have a different sequence to the
+/// one used for the previous results.  Genes without a sequence MD5 in the
+/// previous results count as new.
+pub fn changed_proteins<'a>(previous: &DomainData, proteins: &'a [Protein],
+                            id_mapper: &IdMapper)
+    -> Vec<&'a Protein>
+{
+    let previous_md5s = previous_md5s(previous);
+
+    proteins.iter()
+        .filter(|protein| {
+            let gene_uniquename = id_mapper.gene_id(&protein.id);
+            previous_md5s.get(gene_uniquename.as_str())
+                .is_none_or(|&md5| !md5.eq_ignore_ascii_case(&protein.md5()))
+        })
+        .collect()
+}
+
+/// Add the previous InterPro matches of the unchanged proteins to the
+/// results of a partial InterProScan run.  Genes that have new results and
+/// genes that are no longer in the FASTA file aren't copied.  The TMHMM and
+/// low complexity features of the previous results are discarded as they
+/// are recalculated for all proteins.  Returns the number of genes copied.
+pub fn merge_previous(previous: DomainData, domains_by_id: &mut BTreeMap<String, GeneMatches>,
+                      proteins: &[Protein], id_mapper: &IdMapper)
+    -> usize
+{
+    let unchanged: HashMap<String, String> = {
+        let previous_md5s = previous_md5s(&previous);
+
+        proteins.iter()
+            .filter_map(|protein| {
+                let gene_uniquename = id_mapper.gene_id(&protein.id);
+                let md5 = protein.md5();
+                match previous_md5s.get(gene_uniquename.as_str()) {
+                    Some(previous_md5) if previous_md5.eq_ignore_ascii_case(&md5) =>
+                        Some((gene_uniquename, md5)),
+                    _ => None,
+                }
+            })
+            .collect()
+    };
+
+    let mut copied_count = 0;
+
+    for (gene_uniquename, mut gene_matches) in previous.domains_by_id {
+        if domains_by_id.contains_key(&gene_uniquename) {
+            continue;
+        }
+
+        if let Some(md5) = unchanged.get(&gene_uniquename) {
+            gene_matches.sequence_md5 = Some(md5.clone());
+            gene_matches.tmhmm_matches.clear();
+            gene_matches.tmhmm_topology = None;
+            gene_matches.segmasker_matches.clear();
+            domains_by_id.insert(gene_uniquename, gene_matches);
+            copied_count += 1;
+        }
+    }
+
+    copied_count
+}
@@ -1,22 +1,50 @@
-use std::collections::BTreeMap;
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 use std::cmp::Ordering;
+use std::fmt;
+
+use serde::Deserializer;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::error::{DomainProcessError, Result};
+use crate::id_mapping::IdMapper;
+use crate::interpro_xml::parse_xml;
+use crate::types::{GeneMatches, GoXref, InterProMatch, Location, LocationFragment, MatchLocation,
+                   PathwayXref};
+use crate::util::{merge_locations, open_input, strip_compression_extension};
+
+/// The InterProScan release series that produced an output file.  The JSON
+/// and XML of InterProScan 5 use upper case library and entry type names
+/// (eg. "PFAM" and "DOMAIN") where InterProScan 6 uses "Pfam" and "Domain".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaGeneration {
+    InterProScan5,
+    InterProScan6,
+}
 
-use crate::types::{GeneMatches, InterProMatch, Location};
-use crate::util::merge_locations;
+impl SchemaGeneration {
+    pub fn from_interproscan_version(interproscan_version: &str) -> SchemaGeneration {
+        if interproscan_version.starts_with("5.") || interproscan_version == "5" {
+            SchemaGeneration::InterProScan5
+        } else {
+            SchemaGeneration::InterProScan6
+        }
+    }
 
-#[derive(Debug, Deserialize)]
-pub struct InterProScanOutput {
-    #[serde(rename = "interproscan-version")]
-    pub interproscan_version: String,
-    #[serde(rename = "interpro-version")]
-    pub interpro_version: String,
-    pub results: Vec<InterProScanResult>,
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            SchemaGeneration::InterProScan5 => "IPS5",
+            SchemaGeneration::InterProScan6 => "IPS6",
+        }
+    }
 }
 
+// The results also have the protein sequence, which isn't needed.  It isn't
+// a field so serde skips it without copying it.
 #[derive(Debug, Deserialize)]
 pub struct InterProScanResult {
+    #[serde(default)]
+    pub md5: Option<String>,
     pub matches: Vec<InterProScanMatch>,
     pub xref: Vec<InterProScanXref>,
 }
@@ -27,6 +55,10 @@ pub struct InterProScanMatch {
     pub locations: Vec<InterProScanLocation>,
     #[serde(rename = "model-ac")]
     pub model_ac: String,
+    #[serde(default)]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub evalue: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,6 +90,24 @@ pub struct InterProScanLocation {
     pub location_fragments: Vec<InterProScanLocationFragment>,
     #[serde(rename = "sequence-feature")]
     pub sequence_feature: Option<String>,
+    #[serde(default)]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub evalue: Option<f64>,
+    #[serde(rename = "hmmStart", default)]
+    pub hmm_start: Option<usize>,
+    #[serde(rename = "hmmEnd", default)]
+    pub hmm_end: Option<usize>,
+    #[serde(rename = "hmmLength", default)]
+    pub hmm_length: Option<usize>,
+    #[serde(rename = "hmmBounds", default)]
+    pub hmm_bounds: Option<String>,
+    #[serde(rename = "envelopeStart", default)]
+    pub envelope_start: Option<usize>,
+    #[serde(rename = "envelopeEnd", default)]
+    pub envelope_end: Option<usize>,
+    #[serde(default)]
+    pub representative: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,6 +121,21 @@ pub struct InterProScanXref {
     pub id: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct InterProScanGoXref {
+    pub id: String,
+    pub name: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InterProScanPathwayXref {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(rename = "databaseName")]
+    pub database_name: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct InterProScanEntry {
     pub accession: String,
@@ -78,10 +143,138 @@ pub struct InterProScanEntry {
     pub description: String,
     #[serde(rename = "type")]
     pub entry_type: String,
+    #[serde(rename = "goXRefs", default)]
+    pub go_xrefs: Vec<InterProScanGoXref>,
+    #[serde(rename = "pathwayXRefs", default)]
+    pub pathway_xrefs: Vec<InterProScanPathwayXref>,
 }
 
 pub type VersionString = String;
 
+// InterProScan 5 version strings include the InterPro release, eg. "91.0"
+// from "5.59-91.0"
+pub(crate) fn ips5_interpro_version(interproscan_version: &str) -> Option<String> {
+    let (_, interpro_version) = interproscan_version.split_once('-')?;
+
+    let is_version = !interpro_version.is_empty() &&
+        interpro_version.chars().all(|c| c.is_ascii_digit() || c == '.');
+
+    is_version.then(|| interpro_version.to_owned())
+}
+
+// eg. "v109.0 (InterProScan v6.0.1)"
+pub(crate) fn make_version_string(interpro_version: Option<&str>,
+                                  interproscan_details: &str)
+    -> VersionString
+{
+    match interpro_version {
+        Some(interpro_version) =>
+            format!("v{} (InterProScan {})", interpro_version, interproscan_details),
+        None => format!("unknown (InterProScan {})", interproscan_details),
+    }
+}
+
+
+fn ips5_library_name(library: &str) -> Option<&'static str> {
+    let name = match library {
+        "ANTIFAM" => "AntiFam",
+        "CDD" => "CDD",
+        "COILS" => "COILS",
+        "FUNFAM" => "CATH-FunFam",
+        "GENE3D" => "CATH-Gene3D",
+        "HAMAP" => "HAMAP",
+        "MOBIDB_LITE" => "MobiDB-lite",
+        "NCBIFAM" => "NCBIfam",
+        "PANTHER" => "PANTHER",
+        "PFAM" => "Pfam",
+        "PHOBIUS" => "Phobius",
+        "PIRSF" => "PIRSF",
+        "PIRSR" => "PIRSR",
+        "PRINTS" => "PRINTS",
+        "PRODOM" => "ProDom",
+        "PROSITE_PATTERNS" => "PROSITE patterns",
+        "PROSITE_PROFILES" => "PROSITE profiles",
+        "SFLD" => "SFLD",
+        "SIGNALP_EUK" | "SIGNALP_GRAM_POSITIVE" | "SIGNALP_GRAM_NEGATIVE" => "SignalP",
+        "SMART" => "SMART",
+        "SUPERFAMILY" => "SUPERFAMILY",
+        // TIGRFAMs was merged into NCBIfam after InterProScan 5; keep the
+        // old name so that archived results aren't relabelled
+        "TIGRFAM" => "TIGRFAMs",
+        "TMHMM" => "TMHMM",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+// InterProScan 5 TSV output uses analysis names like "ProSiteProfiles" and
+// "Gene3D".  Convert them to the same names as the JSON and XML output, eg.
+// "PROSITE profiles" and "CATH-Gene3D".  Unknown names (eg. from
+// InterProScan 6, which uses the JSON names) are unchanged.
+fn tsv_library_name(analysis: &str) -> String {
+    let upper_analysis = analysis.to_ascii_uppercase();
+    let ips5_library = match upper_analysis.as_str() {
+        "MOBIDBLITE" => "MOBIDB_LITE",
+        "PROSITEPATTERNS" => "PROSITE_PATTERNS",
+        "PROSITEPROFILES" => "PROSITE_PROFILES",
+        other => other,
+    };
+
+    ips5_library_name(ips5_library)
+        .map(|library| library.to_owned())
+        .unwrap_or_else(|| analysis.to_owned())
+}
+
+// The analyses that report a score instead of an E-value in the TSV output
+fn tsv_score_is_evalue(library: &str) -> bool {
+    !matches!(library, "PROSITE profiles" | "PROSITE patterns" | "HAMAP" | "SignalP")
+}
+
+// "HOMOLOGOUS_SUPERFAMILY" -> "Homologous_superfamily"
+fn ips5_entry_type(entry_type: &str) -> String {
+    let lower = entry_type.to_ascii_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => lower,
+    }
+}
+
+/// Convert library names and entry types from InterProScan 5 output to the
+/// InterProScan 6 style so that both generations give the same dbnames.
+pub(crate) fn normalise_ips5_result(result: &mut InterProScanResult) {
+    for interpro_match in result.matches.iter_mut() {
+        let signature = &mut interpro_match.signature;
+        let library_release = &mut signature.library_release;
+        if let Some(library) = ips5_library_name(&library_release.library) {
+            library_release.library = library.into();
+        }
+        if let Some(ref mut entry) = signature.entry {
+            entry.entry_type = ips5_entry_type(&entry.entry_type);
+        }
+    }
+}
+
+fn entry_go_xrefs(entry: &InterProScanEntry) -> Vec<GoXref> {
+    entry.go_xrefs.iter()
+        .map(|go_xref| GoXref {
+            id: go_xref.id.clone(),
+            name: go_xref.name.clone().filter(|name| !name.is_empty()),
+            category: go_xref.category.clone().filter(|category| !category.is_empty()),
+        })
+        .collect()
+}
+
+fn entry_pathway_xrefs(entry: &InterProScanEntry) -> Vec<PathwayXref> {
+    entry.pathway_xrefs.iter()
+        .map(|pathway_xref| PathwayXref {
+            database: pathway_xref.database_name.clone(),
+            id: pathway_xref.id.clone(),
+            name: pathway_xref.name.clone().filter(|name| !name.is_empty()),
+        })
+        .collect()
+}
 
 fn process_one_result(matches: Vec<InterProScanMatch>)
      -> BTreeMap<String, InterProMatch>
@@ -126,7 +319,7 @@ fn process_one_result(matches: Vec<InterProScanMatch>)
                     }]
                 };
 
-            match_map
+            let match_entry = match_map
                 .entry(match_id.clone())
                 .or_insert_with(|| {
                     let dbname = if signature.accession == "Signal Peptide" {
@@ -136,25 +329,62 @@ fn process_one_result(matches: Vec<InterProScanMatch>)
                     };
                     let interpro_id =
                         signature.entry.as_ref().map(|entry| entry.accession.clone());
-                    let interpro_name =
-                        signature.entry.as_ref().map(|entry| entry.name.clone());
+                    let interpro_name = signature.entry.as_ref()
+                        .map(|entry| entry.name.clone())
+                        .filter(|name| !name.is_empty());
                     let interpro_description =
                         signature.entry.as_ref().map(|entry| entry.description.clone());
+                    let interpro_type = signature.entry.as_ref()
+                        .map(|entry| entry.entry_type.clone())
+                        .filter(|entry_type| !entry_type.is_empty());
+                    let (go_xrefs, pathway_xrefs) = match signature.entry {
+                        Some(ref entry) => (entry_go_xrefs(entry), entry_pathway_xrefs(entry)),
+                        None => (vec![], vec![]),
+                    };
+                    let dbversion = Some(signature.library_release.version.clone())
+                        .filter(|version| !version.is_empty());
                     InterProMatch {
                         id: match_id.clone(),
                         dbname,
+                        dbversion,
                         name: signature.name.clone(),
                         description: signature.description.clone(),
                         interpro_id,
                         interpro_name,
                         interpro_description,
+                        interpro_type,
+                        go_xrefs,
+                        pathway_xrefs,
+                        score: interpro_match.score,
+                        evalue: interpro_match.evalue,
                         match_start: usize::MAX,
                         match_end: 0,
                         locations: vec![],
+                        match_locations: vec![],
                     }
-                })
-                .locations
-                .extend(fragment_locs);
+                });
+
+            match_entry.locations.extend(fragment_locs);
+            match_entry.match_locations.push(MatchLocation {
+                start: loc.start,
+                end: loc.end,
+                score: loc.score,
+                evalue: loc.evalue,
+                hmm_start: loc.hmm_start,
+                hmm_end: loc.hmm_end,
+                hmm_length: loc.hmm_length,
+                hmm_bounds: loc.hmm_bounds,
+                envelope_start: loc.envelope_start,
+                envelope_end: loc.envelope_end,
+                representative: loc.representative,
+                fragments: loc.location_fragments.into_iter()
+                    .map(|frag| LocationFragment {
+                        start: frag.start,
+                        end: frag.end,
+                        dc_status: frag.dc_status.or(frag.dcstatus),
+                    })
+                    .collect(),
+            });
         }
     }
 
@@ -172,93 +402,474 @@ fn process_one_result(matches: Vec<InterProScanMatch>)
     match_map
 }
 
-/// Parse an InterPro TSV file.  Return a map from UniProt ID to struct
-/// containing its InterProMatches.
-pub fn parse(filename: &str)
-         -> (VersionString, BTreeMap<String, GeneMatches>)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Json,
+    Tsv,
+    Xml,
+}
+
+/// Guess the format of an InterProScan output file from its extension,
+/// falling back to looking at the first non-whitespace character.
+pub fn detect_input_format(filename: &str) -> Result<InputFormat> {
+    let lower_filename = strip_compression_extension(filename).to_ascii_lowercase();
+
+    if lower_filename.ends_with(".tsv") {
+        return Ok(InputFormat::Tsv);
+    }
+    if lower_filename.ends_with(".json") {
+        return Ok(InputFormat::Json);
+    }
+    if lower_filename.ends_with(".xml") {
+        return Ok(InputFormat::Xml);
+    }
+
+    let reader = open_input(filename)?;
+
+    for byte_result in reader.bytes() {
+        let byte = byte_result.map_err(|err| DomainProcessError::io(filename, err))?;
+        if !byte.is_ascii_whitespace() {
+            return Ok(match byte {
+                b'{' => InputFormat::Json,
+                b'<' => InputFormat::Xml,
+                _ => InputFormat::Tsv,
+            });
+        }
+    }
+
+    Ok(InputFormat::Json)
+}
+
+/// Parse an InterProScan output file in the given format.
+pub fn parse_with_format(filename: &str, format: InputFormat, id_mapper: &IdMapper)
+         -> Result<(VersionString, BTreeMap<String, GeneMatches>)>
+{
+    match format {
+        InputFormat::Json => parse(filename, id_mapper),
+        InputFormat::Tsv => parse_tsv(filename, id_mapper),
+        InputFormat::Xml => parse_xml(filename, id_mapper),
+    }
+}
+
+/// Parse an InterProScan 5 or 6 JSON file.  Return a map from gene ID to
+/// struct containing its InterProMatches.  The xref IDs of each result are
+/// converted to gene IDs using the `id_mapper`.  The version string includes the
+/// detected InterProScan generation, eg. "v109.0 (InterProScan v6.0.1, IPS6 JSON)"
+pub fn parse(filename: &str, id_mapper: &IdMapper)
+         -> Result<(VersionString, BTreeMap<String, GeneMatches>)>
 {
-    let file = match File::open(filename) {
-        Ok(file) => file,
-        Err(err) => {
-            panic!("Failed to read {}: {}\n", filename, err)
+    let mut results = BTreeMap::new();
+
+    let version = parse_streaming(filename, id_mapper, |gene_matches| {
+        results.insert(gene_matches.gene_uniquename.clone(), gene_matches);
+    })?;
+
+    Ok((version, results))
+}
+
+fn tsv_field(record: &csv::StringRecord, index: usize) -> Option<String> {
+    match record.get(index) {
+        Some(field) if !field.is_empty() && field != "-" => Some(field.to_owned()),
+        _ => None,
+    }
+}
+
+// eg. "GO:0005515(InterPro)|GO:0006355(PANTHER)" or "GO:0005515|GO:0006355"
+fn parse_tsv_go_xrefs(field: &str) -> Vec<InterProScanGoXref> {
+    let mut go_xrefs: Vec<InterProScanGoXref> = vec![];
+
+    for term in field.split('|') {
+        let id = term.split_once('(').map_or(term, |(id, _)| id).trim();
+        if !id.is_empty() && !go_xrefs.iter().any(|go_xref| go_xref.id == id) {
+            go_xrefs.push(InterProScanGoXref {
+                id: id.into(),
+                name: None,
+                category: None,
+            });
+        }
+    }
+
+    go_xrefs
+}
+
+// eg. "Reactome: R-HSA-6811438|MetaCyc: PWY-7511"
+fn parse_tsv_pathway_xrefs(field: &str) -> Vec<InterProScanPathwayXref> {
+    field.split('|')
+        .filter_map(|pathway| pathway.split_once(':'))
+        .map(|(database_name, id)| InterProScanPathwayXref {
+            id: id.trim().into(),
+            name: None,
+            database_name: database_name.trim().into(),
+        })
+        .collect()
+}
+
+/// Parse the TSV output of InterProScan.  The TSV format doesn't record
+/// signature names or the InterPro and InterProScan versions so the
+/// returned version string only records that the input was TSV.
+pub fn parse_tsv(filename: &str, id_mapper: &IdMapper)
+         -> Result<(VersionString, BTreeMap<String, GeneMatches>)>
+{
+    let reader = open_input(filename)?;
+
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(false)
+        .flexible(true)
+        .quoting(false)
+        .comment(Some(b'#'))
+        .from_reader(reader);
+
+    // protein ID -> (library, signature accession) -> match
+    let mut matches_by_protein: BTreeMap<String, BTreeMap<(String, String), InterProScanMatch>> =
+        BTreeMap::new();
+    let mut md5_by_protein: HashMap<String, String> = HashMap::new();
+
+    for record_result in csv_reader.records() {
+        let record = record_result
+            .map_err(|source| DomainProcessError::Tsv {
+                filename: filename.into(),
+                source,
+            })?;
+
+        let line_number = record.position().map(|position| position.line() as usize);
+
+        if record.len() < 9 {
+            return Err(DomainProcessError::malformed(filename, line_number,
+                                                     "too few columns"));
         }
-    };
 
-    let reader = BufReader::new(file);
+        let get_required = |index: usize| {
+            tsv_field(&record, index)
+                .ok_or_else(|| DomainProcessError::malformed(filename, line_number,
+                                                             format!("missing column {}", index + 1)))
+        };
 
-    let mut interproscan_output: InterProScanOutput =
-        match serde_json::from_reader(reader) {
-            Ok(config) => config,
-            Err(err) => {
-                panic!("failed to parse {}: {}", filename, err)
+        let parse_position = |index: usize| {
+            get_required(index)?.parse::<usize>()
+                .map_err(|err| DomainProcessError::malformed(filename, line_number,
+                                                             format!("failed to parse column {}: {}",
+                                                                     index + 1, err)))
+        };
+
+        let protein_id = get_required(0)?;
+        if let Some(md5) = tsv_field(&record, 1) {
+            md5_by_protein.insert(protein_id.clone(), md5);
+        }
+
+        let library = tsv_library_name(&get_required(3)?);
+        let accession = get_required(4)?;
+        let start = parse_position(6)?;
+        let end = parse_position(7)?;
+
+        // column 9 is the E-value, or the score for analyses that don't
+        // report E-values
+        let (score, evalue) = match tsv_field(&record, 8) {
+            Some(field) => {
+                let value = field.parse::<f64>()
+                    .map_err(|err| DomainProcessError::malformed(filename, line_number,
+                                                                 format!("failed to parse column 9: {}",
+                                                                         err)))?;
+                if tsv_score_is_evalue(&library) {
+                    (None, Some(value))
+                } else {
+                    (Some(value), None)
+                }
             },
+            None => (None, None),
         };
 
-    for result in interproscan_output.results.iter_mut() {
-        for interpro_match in result.matches.iter_mut() {
-            if let Some(ref name) = interpro_match.signature.name && name.is_empty() {
-                interpro_match.signature.name = None
+        let entry = tsv_field(&record, 11).map(|entry_accession| {
+            let entry_description = tsv_field(&record, 12).unwrap_or_default();
+            InterProScanEntry {
+                accession: entry_accession,
+                // the TSV format only has the description of the entry
+                name: "".into(),
+                description: entry_description,
+                entry_type: "".into(),
+                go_xrefs: tsv_field(&record, 13).map(|field| parse_tsv_go_xrefs(&field))
+                    .unwrap_or_default(),
+                pathway_xrefs: tsv_field(&record, 14)
+                    .map(|field| parse_tsv_pathway_xrefs(&field))
+                    .unwrap_or_default(),
             }
-        }
+        });
+
+        let description = tsv_field(&record, 5);
+
+        matches_by_protein
+            .entry(protein_id)
+            .or_default()
+            .entry((library.clone(), accession.clone()))
+            .or_insert_with(|| InterProScanMatch {
+                signature: InterProScanSignature {
+                    accession: accession.clone(),
+                    name: None,
+                    description,
+                    library_release: InterProScanSignatureLibraryRelease {
+                        library,
+                        version: "".into(),
+                    },
+                    entry,
+                },
+                locations: vec![],
+                model_ac: accession,
+                score: None,
+                evalue: None,
+            })
+            .locations
+            .push(InterProScanLocation {
+                start,
+                end,
+                location_fragments: vec![],
+                sequence_feature: None,
+                score,
+                evalue,
+                hmm_start: None,
+                hmm_end: None,
+                hmm_length: None,
+                hmm_bounds: None,
+                envelope_start: None,
+                envelope_end: None,
+                representative: false,
+            });
     }
 
-    let mut gene_match_map = BTreeMap::new();
+    let results = matches_by_protein.into_iter()
+        .map(|(protein_id, matches)| InterProScanResult {
+            md5: md5_by_protein.remove(&protein_id),
+            matches: matches.into_values().collect(),
+            xref: vec![InterProScanXref { id: protein_id }],
+        })
+        .collect();
+
+    Ok((make_version_string(None, "TSV output"), gene_matches_from_results(results, id_mapper)))
+}
 
-    for result in interproscan_output.results.into_iter() {
-        let InterProScanResult { matches, xref } = result;
+fn sort_interpro_matches(interpro_matches: &mut [InterProMatch]) {
+    interpro_matches
+        .sort_by(|a, b| {
+            let dbname_cmp = a.dbname.cmp(&b.dbname);
+            if dbname_cmp == Ordering::Equal {
+                Ordering::Equal
+            } else {
+                if a.dbname.to_ascii_lowercase().starts_with("pfam") {
+                    Ordering::Less
+                } else {
+                    if b.dbname.to_ascii_lowercase().starts_with("pfam") {
+                        Ordering::Greater
+                    } else {
+                        dbname_cmp
+                    }
+                }
+            }
+        });
+}
 
-        let match_map = process_one_result(matches);
+/// Process the matches of one InterProScan result and return a GeneMatches
+/// for each of the result's xrefs.  Results without matches are only kept
+/// if they have an MD5, so that the sequence can be checked later.
+fn gene_matches_from_result(result: InterProScanResult, id_mapper: &IdMapper)
+    -> Vec<GeneMatches>
+{
+    let InterProScanResult { md5, mut matches, xref, .. } = result;
 
-        let gene_uniquenames: Vec<_> = xref.iter()
-            .map(|xref| xref.id.replace(".1:pep", ""))
-            .collect();
+    let sequence_md5 = md5.map(|md5| md5.to_ascii_lowercase());
 
-        for gene_uniquename in gene_uniquenames.into_iter() {
-            gene_match_map.insert(gene_uniquename, match_map.clone());
+    for interpro_match in matches.iter_mut() {
+        if let Some(ref name) = interpro_match.signature.name && name.is_empty() {
+            interpro_match.signature.name = None
         }
     }
 
-    let mut results = BTreeMap::new();
+    let match_map = process_one_result(matches);
+
+    if match_map.is_empty() && sequence_md5.is_none() {
+        return vec![];
+    }
 
-    for (gene_uniquename, domains_by_id) in gene_match_map.into_iter() {
-        for mut interpro_match in domains_by_id.into_values() {
+    let mut interpro_matches: Vec<_> = match_map.into_values()
+        .map(|mut interpro_match| {
             interpro_match.locations.sort();
             merge_locations(&mut interpro_match.locations);
-            results
-                .entry(gene_uniquename.clone())
-                .or_insert_with(|| GeneMatches {
-                    gene_uniquename: gene_uniquename.clone(),
-                    interpro_matches: vec![],
-                    segmasker_matches: vec![],
-                    tmhmm_matches: vec![],
-                })
-                .interpro_matches
-                .push(interpro_match);
+            interpro_match.match_locations
+                .sort_by_key(|match_location| (match_location.start, match_location.end));
+            interpro_match
+        })
+        .collect();
+
+    sort_interpro_matches(&mut interpro_matches);
+
+    let mut gene_uniquenames: Vec<_> = xref.iter()
+        .map(|xref| id_mapper.gene_id(&xref.id))
+        .collect();
+
+    // avoid cloning the matches for the common case of one xref
+    let Some(last_gene_uniquename) = gene_uniquenames.pop()
+    else {
+        return vec![];
+    };
+
+    let mut results: Vec<_> = gene_uniquenames.into_iter()
+        .map(|gene_uniquename| GeneMatches {
+            sequence_md5: sequence_md5.clone(),
+            interpro_matches: interpro_matches.clone(),
+            ..GeneMatches::new(gene_uniquename)
+        })
+        .collect();
+
+    results.push(GeneMatches {
+        sequence_md5,
+        interpro_matches,
+        ..GeneMatches::new(last_gene_uniquename)
+    });
+
+    results
+}
+
+pub(crate) fn gene_matches_from_results(interproscan_results: Vec<InterProScanResult>,
+                                        id_mapper: &IdMapper)
+         -> BTreeMap<String, GeneMatches>
+{
+    interproscan_results.into_iter()
+        .flat_map(|result| gene_matches_from_result(result, id_mapper))
+        .map(|gene_matches| (gene_matches.gene_uniquename.clone(), gene_matches))
+        .collect()
+}
+
+// Deserialises the "results" array one element at a time, passing the
+// GeneMatches of each result to the callback
+struct ResultsSeed<'a, F> {
+    generation: Option<SchemaGeneration>,
+    id_mapper: &'a IdMapper,
+    callback: &'a mut F,
+}
+
+impl<'de, F> DeserializeSeed<'de> for ResultsSeed<'_, F>
+    where F: FnMut(GeneMatches)
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F> Visitor<'de> for ResultsSeed<'_, F>
+    where F: FnMut(GeneMatches)
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of InterProScan results")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<(), A::Error>
+        where A: SeqAccess<'de>
+    {
+        while let Some(mut result) = seq.next_element::<InterProScanResult>()? {
+            // normalising is harmless for InterProScan 6 output so do it if
+            // the version hasn't been seen yet
+            if self.generation != Some(SchemaGeneration::InterProScan6) {
+                normalise_ips5_result(&mut result);
+            }
+            for gene_matches in gene_matches_from_result(result, self.id_mapper) {
+                (self.callback)(gene_matches);
+            }
         }
 
-        if let Some(ref mut gene_matches) = results.get_mut(&gene_uniquename) {
-            gene_matches.interpro_matches
-                .sort_by(|a, b| {
-                    let dbname_cmp = a.dbname.cmp(&b.dbname);
-                    if dbname_cmp == Ordering::Equal {
-                        Ordering::Equal
-                    } else {
-                        if a.dbname.to_ascii_lowercase().starts_with("pfam") {
-                            Ordering::Less
-                        } else {
-                            if b.dbname.to_ascii_lowercase().starts_with("pfam") {
-                                Ordering::Greater
-                            } else {
-                                dbname_cmp
-                            }
-                        }
-                    }
-                });
+        Ok(())
+    }
+}
+
+struct OutputVisitor<'a, F> {
+    id_mapper: &'a IdMapper,
+    callback: &'a mut F,
+}
+
+impl<'de, F> Visitor<'de> for OutputVisitor<'_, F>
+    where F: FnMut(GeneMatches)
+{
+    // (InterProScan version, InterPro version)
+    type Value = (String, Option<String>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an InterProScan JSON output object")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+        where A: MapAccess<'de>
+    {
+        let mut interproscan_version: Option<String> = None;
+        let mut interpro_version: Option<String> = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "interproscan-version" => {
+                    interproscan_version = Some(map.next_value()?);
+                },
+                "interpro-version" => {
+                    interpro_version = map.next_value()?;
+                },
+                "results" => {
+                    let generation = interproscan_version.as_deref()
+                        .map(SchemaGeneration::from_interproscan_version);
+                    map.next_value_seed(ResultsSeed {
+                        generation,
+                        id_mapper: self.id_mapper,
+                        callback: &mut *self.callback,
+                    })?;
+                },
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
         }
+
+        let interproscan_version = interproscan_version
+            .ok_or_else(|| serde::de::Error::missing_field("interproscan-version"))?;
+
+        Ok((interproscan_version, interpro_version))
     }
+}
+
+/// Read an InterProScan 5 or 6 JSON file one result at a time, calling
+/// `callback` with the GeneMatches of each gene as it is processed, so
+/// that the whole file is never held in memory.  Returns the version
+/// string, eg. "v109.0 (InterProScan v6.0.1, IPS6 JSON)".
+pub fn parse_streaming<F>(filename: &str, id_mapper: &IdMapper, mut callback: F)
+    -> Result<VersionString>
+    where F: FnMut(GeneMatches)
+{
+    let reader = open_input(filename)?;
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+    let visitor = OutputVisitor {
+        id_mapper,
+        callback: &mut callback,
+    };
+
+    let (interproscan_version, interpro_version) =
+        deserializer.deserialize_map(visitor)
+        .and_then(|versions| {
+            deserializer.end()?;
+            Ok(versions)
+        })
+        .map_err(|source| DomainProcessError::Json {
+            filename: filename.into(),
+            source,
+        })?;
+
+    let generation = SchemaGeneration::from_interproscan_version(&interproscan_version);
+
+    let interpro_version =
+        interpro_version.or_else(|| ips5_interpro_version(&interproscan_version));
 
-    let version = format!("v{} (InterProScan v{})", interproscan_output.interpro_version,
-                          interproscan_output.interproscan_version);
+    let interproscan_details = format!("v{}, {} JSON", interproscan_version,
+                                       generation.short_name());
 
-    (version, results)
+    Ok(make_version_string(interpro_version.as_deref(), &interproscan_details))
 }
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use roxmltree::{Document, Node};
+
+use crate::interpro_parse::{gene_matches_from_results, InterProScanEntry, InterProScanGoXref,
+                            InterProScanLocation, InterProScanLocationFragment,
+                            InterProScanMatch, InterProScanPathwayXref, InterProScanResult,
+                            InterProScanSignature, InterProScanSignatureLibraryRelease,
+                            InterProScanXref, ips5_interpro_version, make_version_string,
+                            normalise_ips5_result, VersionString};
+use crate::error::{DomainProcessError, Result};
+use crate::id_mapping::IdMapper;
+use crate::types::GeneMatches;
+use crate::util::open_input;
+
+fn child_element<'a, 'input>(node: Node<'a, 'input>, name: &str)
+    -> Option<Node<'a, 'input>>
+{
+    node.children().find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn element_children<'a, 'input>(node: Node<'a, 'input>)
+    -> impl Iterator<Item = Node<'a, 'input>>
+{
+    node.children().filter(|child| child.is_element())
+}
+
+fn attribute_string(node: Node, name: &str) -> Option<String> {
+    node.attribute(name)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_owned())
+}
+
+fn malformed(filename: &str, node: Node, message: String) -> DomainProcessError {
+    let line_number = node.document().text_pos_at(node.range().start).row as usize;
+    DomainProcessError::malformed(filename, Some(line_number), message)
+}
+
+fn required_attribute(filename: &str, node: Node, name: &str) -> Result<String> {
+    attribute_string(node, name)
+        .ok_or_else(|| malformed(filename, node,
+                                 format!("missing {} attribute on <{}>",
+                                         name, node.tag_name().name())))
+}
+
+fn parse_position(filename: &str, node: Node, name: &str) -> Result<usize> {
+    let value = required_attribute(filename, node, name)?;
+    value.parse::<usize>()
+        .map_err(|err| malformed(filename, node,
+                                 format!("failed to parse {}=\"{}\": {}", name, value, err)))
+}
+
+// Parse an attribute that InterProScan only writes for some analyses
+fn parse_optional<T>(filename: &str, node: Node, name: &str) -> Result<Option<T>>
+    where T: std::str::FromStr,
+          T::Err: std::fmt::Display,
+{
+    attribute_string(node, name)
+        .map(|value| {
+            value.parse::<T>()
+                .map_err(|err| malformed(filename, node,
+                                         format!("failed to parse {}=\"{}\": {}",
+                                                 name, value, err)))
+        })
+        .transpose()
+}
+
+fn parse_entry(filename: &str, entry_node: Node) -> Result<InterProScanEntry> {
+    Ok(InterProScanEntry {
+        accession: required_attribute(filename, entry_node, "ac")?,
+        name: attribute_string(entry_node, "name").unwrap_or_default(),
+        description: attribute_string(entry_node, "desc").unwrap_or_default(),
+        entry_type: attribute_string(entry_node, "type").unwrap_or_default(),
+        go_xrefs: element_children(entry_node)
+            .filter(|child| child.tag_name().name() == "go-xref")
+            .map(|xref_node| Ok(InterProScanGoXref {
+                id: required_attribute(filename, xref_node, "id")?,
+                name: attribute_string(xref_node, "name"),
+                category: attribute_string(xref_node, "category"),
+            }))
+            .collect::<Result<_>>()?,
+        pathway_xrefs: element_children(entry_node)
+            .filter(|child| child.tag_name().name() == "pathway-xref")
+            .map(|xref_node| Ok(InterProScanPathwayXref {
+                id: required_attribute(filename, xref_node, "id")?,
+                name: attribute_string(xref_node, "name"),
+                database_name: attribute_string(xref_node, "db").unwrap_or_default(),
+            }))
+            .collect::<Result<_>>()?,
+    })
+}
+
+fn parse_signature(filename: &str, signature_node: Node) -> Result<InterProScanSignature> {
+    let accession = required_attribute(filename, signature_node, "ac")?;
+
+    let release_node = child_element(signature_node, "signature-library-release")
+        .ok_or_else(|| malformed(filename, signature_node,
+                                 format!("no <signature-library-release> for {}", accession)))?;
+
+    let entry = child_element(signature_node, "entry")
+        .map(|entry_node| parse_entry(filename, entry_node))
+        .transpose()?;
+
+    Ok(InterProScanSignature {
+        accession,
+        name: attribute_string(signature_node, "name"),
+        description: attribute_string(signature_node, "desc"),
+        library_release: InterProScanSignatureLibraryRelease {
+            library: attribute_string(release_node, "library").unwrap_or_default(),
+            version: attribute_string(release_node, "version").unwrap_or_default(),
+        },
+        entry,
+    })
+}
+
+fn parse_location(filename: &str, location_node: Node) -> Result<InterProScanLocation> {
+    let location_fragments =
+        if let Some(fragments_node) = child_element(location_node, "location-fragments") {
+            element_children(fragments_node)
+                .map(|fragment_node| Ok(InterProScanLocationFragment {
+                    start: parse_position(filename, fragment_node, "start")?,
+                    end: parse_position(filename, fragment_node, "end")?,
+                    dc_status: attribute_string(fragment_node, "dc-status"),
+                    dcstatus: None,
+                }))
+                .collect::<Result<_>>()?
+        } else {
+            vec![]
+        };
+
+    Ok(InterProScanLocation {
+        start: parse_position(filename, location_node, "start")?,
+        end: parse_position(filename, location_node, "end")?,
+        location_fragments,
+        sequence_feature: attribute_string(location_node, "sequence-feature"),
+        score: parse_optional(filename, location_node, "score")?,
+        evalue: parse_optional(filename, location_node, "evalue")?,
+        hmm_start: parse_optional(filename, location_node, "hmm-start")?,
+        hmm_end: parse_optional(filename, location_node, "hmm-end")?,
+        hmm_length: parse_optional(filename, location_node, "hmm-length")?,
+        hmm_bounds: attribute_string(location_node, "hmm-bounds"),
+        envelope_start: parse_optional(filename, location_node, "env-start")?,
+        envelope_end: parse_optional(filename, location_node, "env-end")?,
+        representative: parse_optional(filename, location_node, "representative")?
+            .unwrap_or(false),
+    })
+}
+
+// The match elements are named after the analysis type, eg. <hmmer3-match>
+// or <profilescan-match>, so we only check the suffix
+fn parse_match(filename: &str, match_node: Node) -> Result<InterProScanMatch> {
+    let signature_node = child_element(match_node, "signature")
+        .ok_or_else(|| malformed(filename, match_node,
+                                 format!("<{}> without a <signature>",
+                                         match_node.tag_name().name())))?;
+    let signature = parse_signature(filename, signature_node)?;
+
+    let model_ac = child_element(match_node, "model-ac")
+        .and_then(|model_ac_node| model_ac_node.text())
+        .map(|model_ac| model_ac.trim().to_owned())
+        .unwrap_or_else(|| signature.accession.clone());
+
+    let locations =
+        if let Some(locations_node) = child_element(match_node, "locations") {
+            element_children(locations_node)
+                .filter(|location_node| location_node.tag_name().name().ends_with("-location"))
+                .map(|location_node| parse_location(filename, location_node))
+                .collect::<Result<_>>()?
+        } else {
+            vec![]
+        };
+
+    Ok(InterProScanMatch {
+        signature,
+        locations,
+        model_ac,
+        score: parse_optional(filename, match_node, "score")?,
+        evalue: parse_optional(filename, match_node, "evalue")?,
+    })
+}
+
+fn parse_protein(filename: &str, protein_node: Node) -> Result<InterProScanResult> {
+    let matches =
+        if let Some(matches_node) = child_element(protein_node, "matches") {
+            element_children(matches_node)
+                .filter(|match_node| match_node.tag_name().name().ends_with("-match"))
+                .map(|match_node| parse_match(filename, match_node))
+                .collect::<Result<_>>()?
+        } else {
+            vec![]
+        };
+
+    let xref = element_children(protein_node)
+        .filter(|child| child.tag_name().name() == "xref")
+        .filter_map(|xref_node| attribute_string(xref_node, "id"))
+        .map(|id| InterProScanXref { id })
+        .collect();
+
+    // only the MD5 of the sequence is used
+    let sequence_node = child_element(protein_node, "sequence");
+
+    Ok(InterProScanResult {
+        md5: sequence_node.and_then(|node| attribute_string(node, "md5")),
+        matches,
+        xref,
+    })
+}
+
+/// Parse the XML output of InterProScan 5.  Return the version string and
+/// a map from gene ID to its InterPro matches, as for
+/// [`interpro_parse::parse`](crate::interpro_parse::parse).
+pub fn parse_xml(filename: &str, id_mapper: &IdMapper)
+         -> Result<(VersionString, BTreeMap<String, GeneMatches>)>
+{
+    let mut contents = String::new();
+
+    open_input(filename)?
+        .read_to_string(&mut contents)
+        .map_err(|err| DomainProcessError::io(filename, err))?;
+
+    let document = Document::parse(&contents)
+        .map_err(|source| DomainProcessError::Xml {
+            filename: filename.into(),
+            source,
+        })?;
+
+    let root = document.root_element();
+
+    let interproscan_version =
+        attribute_string(root, "interproscan-version").unwrap_or_else(|| "unknown".into());
+    let interpro_version = attribute_string(root, "interpro-version")
+        .or_else(|| ips5_interpro_version(&interproscan_version));
+
+    // only InterProScan 5 writes XML
+    let results = element_children(root)
+        .filter(|child| child.tag_name().name() == "protein")
+        .map(|protein_node| {
+            let mut result = parse_protein(filename, protein_node)?;
+            normalise_ips5_result(&mut result);
+            Ok(result)
+        })
+        .collect::<Result<_>>()?;
+
+    let version = make_version_string(interpro_version.as_deref(),
+                                      &format!("v{}", interproscan_version));
+
+    Ok((version, gene_matches_from_results(results, id_mapper)))
+}
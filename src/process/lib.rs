@@ -2,7 +2,22 @@ extern crate serde;
 #[macro_use] extern crate serde_derive;
 extern crate csv;
 
+pub mod error;
 pub mod types;
+pub mod id_mapping;
 pub mod interpro_parse;
+pub mod interpro_xml;
+pub mod fasta;
 pub mod segmasker;
+pub mod tmhmm;
+pub mod seg;
+pub mod validate;
+pub mod filter;
+pub mod entries;
+pub mod architecture;
+pub mod incremental;
+pub mod diff;
+pub mod stats;
+pub mod gaf;
+pub mod gff3;
 pub mod util;
@@ -0,0 +1,265 @@
+//! A native implementation of the SEG low complexity algorithm of Wootton
+//! and Federhen (Computers & Chemistry 17:149-163, 1993), following the
+//! NCBI seg/segmasker implementation so that the results can be used in
+//! place of running segmasker.
+
+use std::collections::HashMap;
+
+use crate::fasta::Protein;
+use crate::id_mapping::IdMapper;
+use crate::types::Location;
+
+const AMINO_ACIDS: &[u8; 20] = b"ACDEFGHIKLMNPQRSTVWY";
+
+// the longest stretch that is trimmed from a raw segment
+const MAX_TRIM: usize = 100;
+
+/// SEG parameters, with defaults matching segmasker
+#[derive(Debug, Clone, Copy)]
+pub struct SegParameters {
+    /// The length of the window used to find low complexity triggers
+    pub window: usize,
+    /// Windows with complexity at or below this value trigger a segment
+    pub locut: f64,
+    /// Segments are extended while the complexity is at or below this value
+    pub hicut: f64,
+}
+
+impl Default for SegParameters {
+    fn default() -> SegParameters {
+        SegParameters {
+            window: 12,
+            locut: 2.2,
+            hicut: 2.5,
+        }
+    }
+}
+
+fn residue_index(residue: u8) -> Option<usize> {
+    let upper = residue.to_ascii_uppercase();
+    AMINO_ACIDS.iter().position(|&aa| aa == upper)
+}
+
+// Shannon entropy in bits of the residues in a window, or None if the
+// window contains a residue other than the standard 20
+fn window_entropy(window: &[Option<usize>]) -> Option<f64> {
+    let mut counts = [0usize; 20];
+
+    for residue in window {
+        counts[(*residue)?] += 1;
+    }
+
+    let total = window.len() as f64;
+
+    Some(counts.iter()
+         .filter(|&&count| count > 0)
+         .map(|&count| {
+             let p = count as f64 / total;
+             -p * p.log2()
+         })
+         .sum())
+}
+
+struct Seg<'a> {
+    params: &'a SegParameters,
+    // ln(n!) for n up to the sequence length
+    ln_factorials: Vec<f64>,
+}
+
+impl Seg<'_> {
+    // ln of the number of ways of assigning the observed counts to the
+    // 20 residue types (lnass() in seg.c)
+    fn ln_assignments(&self, sorted_counts: &[usize; 20]) -> f64 {
+        let lnfac = &self.ln_factorials;
+        let mut ans = lnfac[20];
+
+        if sorted_counts[0] == 0 {
+            return ans;
+        }
+
+        let mut total = 20;
+        let mut class = 1;
+        let mut prev = sorted_counts[0];
+
+        for &count in &sorted_counts[1..] {
+            if count == prev {
+                class += 1;
+            } else {
+                total -= class;
+                ans -= lnfac[class];
+                if count == 0 {
+                    return ans - lnfac[total];
+                }
+                class = 1;
+                prev = count;
+            }
+        }
+
+        ans - lnfac[class]
+    }
+
+    // ln of the probability of a segment with the given residue counts
+    // (getprob() in seg.c)
+    fn ln_probability(&self, counts: &[usize; 20], total: usize) -> f64 {
+        let mut sorted_counts = *counts;
+        sorted_counts.sort_unstable_by(|a, b| b.cmp(a));
+
+        let ln_permutations = self.ln_factorials[total] -
+            sorted_counts.iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| self.ln_factorials[count])
+            .sum::<f64>();
+
+        self.ln_assignments(&sorted_counts) + ln_permutations -
+            (total as f64) * 20f64.ln()
+    }
+
+    // Find the subsegment with the lowest probability, returning its
+    // start and end relative to the segment
+    fn trim(&self, segment: &[Option<usize>]) -> (usize, usize) {
+        let len = segment.len();
+        let min_len = if len > MAX_TRIM + 1 { len - MAX_TRIM } else { 1 };
+
+        let mut best = (0, len - 1);
+        let mut min_prob = 1.0;
+
+        for sub_len in (min_len + 1..=len).rev() {
+            let mut counts = [0usize; 20];
+            for residue in segment[..sub_len].iter().flatten() {
+                counts[*residue] += 1;
+            }
+
+            for start in 0..=(len - sub_len) {
+                if start > 0 {
+                    if let Some(residue) = segment[start - 1] {
+                        counts[residue] -= 1;
+                    }
+                    if let Some(residue) = segment[start + sub_len - 1] {
+                        counts[residue] += 1;
+                    }
+                }
+
+                let prob = self.ln_probability(&counts, sub_len);
+                if prob < min_prob {
+                    min_prob = prob;
+                    best = (start, start + sub_len - 1);
+                }
+            }
+        }
+
+        best
+    }
+
+    // Find the low complexity segments of a sequence, adding them to
+    // `segments` offset by `offset` (segseq() in seg.c)
+    fn segments(&self, sequence: &[Option<usize>], offset: usize,
+                segments: &mut Vec<Location>)
+    {
+        let window = self.params.window;
+
+        if window == 0 || sequence.len() < window {
+            return;
+        }
+
+        // entropies[i] is the entropy of the window starting at position i
+        let entropies: Vec<_> = sequence.windows(window).map(window_entropy).collect();
+
+        let is_low = |idx: usize, cutoff: f64| {
+            matches!(entropies[idx], Some(entropy) if entropy <= cutoff)
+        };
+
+        let last = entropies.len() - 1;
+        // segments aren't extended back into the previous segment
+        let mut low_limit = 0;
+        let mut i = 0;
+
+        while i <= last {
+            if is_low(i, self.params.locut) {
+                let mut lo = i;
+                while lo > low_limit && is_low(lo - 1, self.params.hicut) {
+                    lo -= 1;
+                }
+                let mut hi = i;
+                while hi < last && is_low(hi + 1, self.params.hicut) {
+                    hi += 1;
+                }
+
+                let raw_start = lo;
+                let raw_end = hi + window - 1;
+
+                let (trim_start, trim_end) = self.trim(&sequence[raw_start..=raw_end]);
+                let seg_start = raw_start + trim_start;
+                let seg_end = raw_start + trim_end;
+
+                // look for more segments if the trigger window was trimmed
+                // from the left
+                if i + window - 1 < seg_start {
+                    self.segments(&sequence[raw_start..seg_start], offset + raw_start,
+                                  segments);
+                }
+
+                segments.push(Location {
+                    start: seg_start + offset,
+                    end: seg_end + offset,
+                });
+
+                i = hi.min(seg_end);
+                low_limit = i + 1;
+            }
+
+            i += 1;
+        }
+    }
+}
+
+/// Return the low complexity regions of a protein sequence.  As in the
+/// segmasker output the coordinates are zero-based and inclusive.
+pub fn seg_sequence(sequence: &[u8], params: &SegParameters) -> Vec<Location> {
+    let residues: Vec<_> = sequence.iter().map(|&residue| residue_index(residue)).collect();
+
+    let mut ln_factorials = Vec::with_capacity(residues.len().max(20) + 1);
+    ln_factorials.push(0.0);
+    for n in 1..=residues.len().max(20) {
+        ln_factorials.push(ln_factorials[n - 1] + (n as f64).ln());
+    }
+
+    let seg = Seg {
+        params,
+        ln_factorials,
+    };
+
+    let mut segments = vec![];
+    seg.segments(&residues, 0, &mut segments);
+
+    segments.sort();
+
+    // merge overlapping segments
+    let mut merged: Vec<Location> = vec![];
+    for segment in segments {
+        match merged.last_mut() {
+            Some(prev) if prev.end >= segment.start => {
+                prev.end = prev.end.max(segment.end);
+            },
+            _ => merged.push(segment),
+        }
+    }
+
+    merged
+}
+
+/// Run SEG on proteins, returning the same map from gene ID to low
+/// complexity regions as [`segmasker::parse`](crate::segmasker::parse).
+pub fn run(proteins: &[Protein], params: &SegParameters, id_mapper: &IdMapper)
+    -> HashMap<String, Vec<Location>>
+{
+    let mut ret = HashMap::new();
+
+    for protein in proteins {
+        let locations = seg_sequence(protein.sequence.as_bytes(), params);
+        if !locations.is_empty() {
+            ret.insert(id_mapper.gene_id(&protein.id), locations);
+        }
+    }
+
+    ret
+}
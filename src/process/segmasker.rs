@@ -3,30 +3,43 @@ use std::io::BufRead;
 
 use regex::Regex;
 
+use crate::error::{DomainProcessError, Result};
+use crate::id_mapping::IdMapper;
 use crate::types::Location;
 
-pub fn parse(buf_reader: &mut dyn BufRead)
-    -> HashMap<String, Vec<Location>>
+/// Parse segmasker interval output, returning a map from gene ID to the
+/// low complexity regions of its protein.  The ID mapper converts the
+/// protein IDs in the FASTA headers to gene IDs.
+pub fn parse(buf_reader: &mut dyn BufRead, id_mapper: &IdMapper)
+    -> Result<HashMap<String, Vec<Location>>>
 {
     let mut ret = HashMap::new();
 
-    let gene_re = Regex::new(r"^>(\S+)\.1:pep").unwrap();
+    let protein_id_re = Regex::new(r"^>(\S+)").unwrap();
 
     let mut current_gene = String::from("");
 
-    for line_result in buf_reader.lines() {
-        let line = line_result.unwrap();
+    for (idx, line_result) in buf_reader.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line_result
+            .map_err(|err| DomainProcessError::tool_failure("segmasker",
+                                                            format!("failed to read output: {}", err)))?;
+
+        let malformed = || DomainProcessError::MalformedSegmaskerLine {
+            line_number,
+            line: line.clone(),
+        };
+
         if line.starts_with(">") {
-            let re_result = gene_re.captures(&line);
-            let captures = re_result.unwrap_or_else(|| panic!("failed to segmasker output: {}", line));
-            current_gene = captures.get(1).unwrap().as_str().to_owned();
+            let captures = protein_id_re.captures(&line).ok_or_else(malformed)?;
+            current_gene = id_mapper.gene_id(captures.get(1).unwrap().as_str());
         } else {
             let line_parts: Vec<_> = line.split(" ").collect();
-            if *line_parts.get(1).unwrap() != "-" {
-                panic!("can't parse line from segmasker: {}", line);
+            if line_parts.len() != 3 || line_parts[1] != "-" {
+                return Err(malformed());
             }
-            let start = line_parts.first().unwrap().parse::<usize>().unwrap();
-            let end = line_parts.get(2).unwrap().parse::<usize>().unwrap();
+            let start = line_parts[0].parse::<usize>().map_err(|_| malformed())?;
+            let end = line_parts[2].parse::<usize>().map_err(|_| malformed())?;
             ret.entry(current_gene.clone())
                 .or_insert(vec![])
                 .push(Location {
@@ -35,5 +48,5 @@ pub fn parse(buf_reader: &mut dyn BufRead)
                 });
         }
     }
-    ret
+    Ok(ret)
 }
@@ -0,0 +1,213 @@
+//! Summary statistics of processed results, for release notes
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::fasta::Protein;
+use crate::id_mapping::IdMapper;
+use crate::types::{DomainData, GeneMatches, Location};
+
+/// Match counts for one member database
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseStats {
+    pub match_count: usize,
+    pub gene_count: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DomainStats {
+    pub interproscan_version: String,
+    /// The number of genes in the results, plus genes from the FASTA file
+    /// that aren't in the results
+    pub gene_count: usize,
+    pub genes_with_interpro_match: usize,
+    pub by_dbname: BTreeMap<String, DatabaseStats>,
+    pub distinct_interpro_ids: usize,
+    pub genes_with_tm_helices: usize,
+    /// Map from number of helices to number of genes with that many
+    pub tm_helix_count_distribution: BTreeMap<usize, usize>,
+    pub genes_without_features: usize,
+    pub low_complexity_residues: usize,
+    /// Residues covered by MobiDB disorder predictions
+    pub disorder_residues: usize,
+    /// The total length of the proteins, if a FASTA file was given
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub total_residues: Option<usize>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub low_complexity_fraction: Option<f64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub disorder_fraction: Option<f64>,
+}
+
+// The number of residues covered by the union of the locations
+fn covered_residues(locations: impl Iterator<Item = Location>) -> usize {
+    let mut locations: Vec<_> = locations.collect();
+    locations.sort();
+
+    let mut covered = 0;
+    let mut current: Option<Location> = None;
+
+    for location in locations {
+        match current {
+            Some(ref mut current) if location.start <= current.end + 1 => {
+                current.end = current.end.max(location.end);
+            },
+            _ => {
+                if let Some(prev) = current.replace(location) {
+                    covered += prev.end - prev.start + 1;
+                }
+            }
+        }
+    }
+
+    if let Some(last) = current {
+        covered += last.end - last.start + 1;
+    }
+
+    covered
+}
+
+fn has_features(gene_matches: &GeneMatches) -> bool {
+    !gene_matches.interpro_matches.is_empty() || !gene_matches.tmhmm_matches.is_empty() ||
+        !gene_matches.segmasker_matches.is_empty()
+}
+
+/// Calculate the statistics of the results.  If the proteins are given,
+/// the residue fractions are calculated and genes that aren't in the
+/// results are counted as genes without features.
+pub fn stats(domain_data: &DomainData, proteins: Option<&[Protein]>, id_mapper: &IdMapper)
+    -> DomainStats
+{
+    let mut by_dbname: BTreeMap<String, DatabaseStats> = BTreeMap::new();
+    let mut interpro_ids = BTreeSet::new();
+    let mut tm_helix_count_distribution = BTreeMap::new();
+    let mut genes_with_interpro_match = 0;
+    let mut genes_with_tm_helices = 0;
+    let mut genes_without_features = 0;
+    let mut low_complexity_residues = 0;
+    let mut disorder_residues = 0;
+
+    for gene_matches in domain_data.domains_by_id.values() {
+        if !has_features(gene_matches) {
+            genes_without_features += 1;
+            continue;
+        }
+
+        if !gene_matches.interpro_matches.is_empty() {
+            genes_with_interpro_match += 1;
+        }
+
+        let mut gene_dbnames = BTreeSet::new();
+
+        for interpro_match in &gene_matches.interpro_matches {
+            by_dbname.entry(interpro_match.dbname.clone()).or_default().match_count += 1;
+            gene_dbnames.insert(&interpro_match.dbname);
+            if let Some(ref interpro_id) = interpro_match.interpro_id {
+                interpro_ids.insert(interpro_id);
+            }
+        }
+
+        for dbname in gene_dbnames {
+            if let Some(database_stats) = by_dbname.get_mut(dbname) {
+                database_stats.gene_count += 1;
+            }
+        }
+
+        let helix_count = gene_matches.tmhmm_matches.len();
+        if helix_count > 0 {
+            genes_with_tm_helices += 1;
+            *tm_helix_count_distribution.entry(helix_count).or_insert(0) += 1;
+        }
+
+        low_complexity_residues +=
+            covered_residues(gene_matches.segmasker_matches.iter().cloned());
+
+        disorder_residues += covered_residues(
+            gene_matches.interpro_matches.iter()
+                .filter(|interpro_match| interpro_match.dbname.starts_with("MobiDB"))
+                .flat_map(|interpro_match| interpro_match.locations.iter().cloned()));
+    }
+
+    let mut gene_count = domain_data.domains_by_id.len();
+
+    let total_residues = proteins.map(|proteins| {
+        let fasta_genes: BTreeSet<_> = proteins.iter()
+            .map(|protein| id_mapper.gene_id(&protein.id))
+            .collect();
+        let missing_count = fasta_genes.iter()
+            .filter(|gene_uniquename| !domain_data.domains_by_id.contains_key(*gene_uniquename))
+            .count();
+
+        gene_count += missing_count;
+        genes_without_features += missing_count;
+
+        proteins.iter().map(|protein| protein.len()).sum::<usize>()
+    });
+
+    let fraction = |residues: usize| {
+        total_residues
+            .filter(|&total| total > 0)
+            .map(|total| residues as f64 / total as f64)
+    };
+
+    DomainStats {
+        interproscan_version: domain_data.interproscan_version.clone(),
+        gene_count,
+        genes_with_interpro_match,
+        by_dbname,
+        distinct_interpro_ids: interpro_ids.len(),
+        genes_with_tm_helices,
+        tm_helix_count_distribution,
+        genes_without_features,
+        low_complexity_residues,
+        disorder_residues,
+        total_residues,
+        low_complexity_fraction: fraction(low_complexity_residues),
+        disorder_fraction: fraction(disorder_residues),
+    }
+}
+
+/// Write the statistics as TSV with three columns: the category, the name
+/// of the statistic (eg. the database name) and the value
+pub fn write_tsv(stats: &DomainStats, writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(writer, "category\tname\tvalue")?;
+    writeln!(writer, "summary\tinterproscan_version\t{}", stats.interproscan_version)?;
+
+    let mut write_summary = |name: &str, value: &dyn std::fmt::Display| {
+        writeln!(writer, "summary\t{}\t{}", name, value)
+    };
+
+    write_summary("genes", &stats.gene_count)?;
+    write_summary("genes_with_interpro_match", &stats.genes_with_interpro_match)?;
+    write_summary("distinct_interpro_ids", &stats.distinct_interpro_ids)?;
+    write_summary("genes_with_tm_helices", &stats.genes_with_tm_helices)?;
+    write_summary("genes_without_features", &stats.genes_without_features)?;
+    write_summary("low_complexity_residues", &stats.low_complexity_residues)?;
+    write_summary("disorder_residues", &stats.disorder_residues)?;
+
+    if let Some(total_residues) = stats.total_residues {
+        write_summary("total_residues", &total_residues)?;
+    }
+    if let Some(fraction) = stats.low_complexity_fraction {
+        write_summary("low_complexity_fraction", &format!("{:.4}", fraction))?;
+    }
+    if let Some(fraction) = stats.disorder_fraction {
+        write_summary("disorder_fraction", &format!("{:.4}", fraction))?;
+    }
+
+    for (dbname, database_stats) in &stats.by_dbname {
+        writeln!(writer, "dbname_matches\t{}\t{}", dbname, database_stats.match_count)?;
+        writeln!(writer, "dbname_genes\t{}\t{}", dbname, database_stats.gene_count)?;
+    }
+
+    for (helix_count, gene_count) in &stats.tm_helix_count_distribution {
+        writeln!(writer, "tm_helix_count_genes\t{}\t{}", helix_count, gene_count)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_json(stats: &DomainStats, writer: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, stats)?;
+    writeln!(writer)
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+
+use crate::error::{DomainProcessError, Result};
+use crate::fasta::Protein;
+use crate::id_mapping::IdMapper;
+use crate::types::{TMHMMTopology, TMRegion, TMSegment};
+
+// The values from the "#" lines and the segments of one protein
+#[derive(Default)]
+struct ProteinResult {
+    helix_count: Option<usize>,
+    expected_helix_residues: Option<f64>,
+    expected_first_60_residues: Option<f64>,
+    n_in_probability: Option<f64>,
+    possible_signal_sequence: bool,
+    segments: Vec<TMSegment>,
+}
+
+fn parse_region(region: &str) -> Option<TMRegion> {
+    if region.eq_ignore_ascii_case("inside") {
+        Some(TMRegion::Inside)
+    } else if region.eq_ignore_ascii_case("outside") {
+        Some(TMRegion::Outside)
+    } else if region.eq_ignore_ascii_case("TMhelix") {
+        Some(TMRegion::TMHelix)
+    } else {
+        None
+    }
+}
+
+/// Parse TMHMM 2.0 output in the default long format, like:
+///
+/// ```text
+/// # SPAC1.01 Length: 163
+/// # SPAC1.01 Number of predicted TMHs:  1
+/// # SPAC1.01 Exp number of AAs in TMHs: 22.39
+/// # SPAC1.01 Exp number, first 60 AAs:  22.37
+/// # SPAC1.01 Total prob of N-in:        0.99
+/// # SPAC1.01 POSSIBLE N-term signal sequence
+/// SPAC1.01  TMHMM2.0  inside       1    20
+/// SPAC1.01  TMHMM2.0  TMhelix     21    43
+/// SPAC1.01  TMHMM2.0  outside     44   163
+/// ```
+///
+/// Returns a map from protein ID to topology.  Proteins without a
+/// predicted helix aren't included.
+pub fn parse(reader: &mut dyn BufRead, source_name: &str)
+    -> Result<HashMap<String, TMHMMTopology>>
+{
+    let mut results: HashMap<String, ProteinResult> = HashMap::new();
+
+    for (idx, line_result) in reader.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line_result.map_err(|err| DomainProcessError::io(source_name, err))?;
+
+        let malformed = |message: &str| {
+            DomainProcessError::malformed(source_name, Some(line_number), message)
+        };
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let comment = comment.trim();
+            let Some((protein_id, rest)) = comment.split_once(char::is_whitespace)
+            else {
+                continue;
+            };
+            let rest = rest.trim();
+
+            let value = |name: &str| {
+                rest.strip_prefix(name)
+                    .map(|value| value.trim().parse::<f64>()
+                         .map_err(|_| malformed(&format!("can't parse value of \"{}\"", name))))
+                    .transpose()
+            };
+
+            const HELIX_COUNT_NAME: &str = "Number of predicted TMHs:";
+
+            if let Some(helix_count) = rest.strip_prefix(HELIX_COUNT_NAME) {
+                let helix_count = helix_count.trim().parse::<usize>()
+                    .map_err(|_| malformed(&format!("can't parse value of \"{}\"",
+                                                    HELIX_COUNT_NAME)))?;
+                results.entry(protein_id.into()).or_default().helix_count = Some(helix_count);
+            } else if let Some(residues) = value("Exp number of AAs in TMHs:")? {
+                results.entry(protein_id.into()).or_default().expected_helix_residues =
+                    Some(residues);
+            } else if let Some(residues) = value("Exp number, first 60 AAs:")? {
+                results.entry(protein_id.into()).or_default().expected_first_60_residues =
+                    Some(residues);
+            } else if let Some(probability) = value("Total prob of N-in:")? {
+                results.entry(protein_id.into()).or_default().n_in_probability =
+                    Some(probability);
+            } else if rest.starts_with("POSSIBLE N-term signal sequence") {
+                results.entry(protein_id.into()).or_default().possible_signal_sequence = true;
+            }
+
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parts: Vec<_> = line.split_whitespace().collect();
+
+        let [protein_id, _, region, start, end] = parts.as_slice()
+        else {
+            return Err(malformed("expected five columns"));
+        };
+
+        let region = parse_region(region)
+            .ok_or_else(|| malformed(&format!("unknown region: {}", region)))?;
+        let start = start.parse::<usize>()
+            .map_err(|_| malformed("can't parse start position"))?;
+        let end = end.parse::<usize>()
+            .map_err(|_| malformed("can't parse end position"))?;
+
+        results.entry((*protein_id).into()).or_default()
+            .segments.push(TMSegment {
+                region,
+                start,
+                end,
+            });
+    }
+
+    let topologies = results.into_iter()
+        .filter_map(|(protein_id, result)| {
+            let segment_helix_count = result.segments.iter()
+                .filter(|segment| segment.region == TMRegion::TMHelix)
+                .count();
+
+            if segment_helix_count == 0 {
+                return None;
+            }
+
+            let topology = TMHMMTopology {
+                helix_count: result.helix_count.unwrap_or(segment_helix_count),
+                expected_helix_residues: result.expected_helix_residues,
+                expected_first_60_residues: result.expected_first_60_residues,
+                n_in_probability: result.n_in_probability,
+                possible_signal_sequence: result.possible_signal_sequence,
+                segments: result.segments,
+            };
+
+            Some((protein_id, topology))
+        })
+        .collect();
+
+    Ok(topologies)
+}
+
+/// Return a map from gene ID to the topology of the longest protein of the
+/// gene, which is the protein that validate::protein_lengths() uses.  If
+/// several proteins have the same length, the first in the FASTA file is
+/// used.  Genes whose longest protein has no predicted helix aren't
+/// included.
+pub fn topologies_by_gene(mut topologies: HashMap<String, TMHMMTopology>,
+                          proteins: &[Protein], id_mapper: &IdMapper)
+    -> BTreeMap<String, TMHMMTopology>
+{
+    let mut longest_proteins: HashMap<String, &Protein> = HashMap::new();
+
+    for protein in proteins {
+        let longest = longest_proteins.entry(id_mapper.gene_id(&protein.id))
+            .or_insert(protein);
+        if protein.len() > longest.len() {
+            *longest = protein;
+        }
+    }
+
+    longest_proteins.into_iter()
+        .filter_map(|(gene_uniquename, protein)| {
+            topologies.remove(&protein.id)
+                .map(|topology| (gene_uniquename, topology))
+        })
+        .collect()
+}
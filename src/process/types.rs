@@ -1,7 +1,10 @@
 
 use std::{cmp::Ordering, collections::BTreeMap, hash::{Hash, Hasher}};
 
-#[derive(Serialize, Debug, Clone)]
+use crate::error::{DomainProcessError, Result};
+use crate::util::{open_input, OutputWriter};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Location {
     pub start: usize,
     pub end: usize,
@@ -37,10 +40,80 @@ impl Ord for Location {
  }
 
 
-#[derive(Serialize, Debug, Clone)]
+/// A part of a match location.  A discontinuous domain has a fragment on
+/// each side of an insertion.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LocationFragment {
+    pub start: usize,
+    pub end: usize,
+    /// "CONTINUOUS", "C_TERMINAL_DISC", "N_TERMINAL_DISC" or
+    /// "NC_TERMINAL_DISC"
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub dc_status: Option<String>,
+}
+
+/// One location of a member database match, with the scores and model
+/// coordinates reported by InterProScan.  Which of the optional fields are
+/// set depends on the analysis, eg. only HMMER based analyses have HMM
+/// boundaries.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchLocation {
+    pub start: usize,
+    pub end: usize,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub evalue: Option<f64>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub hmm_start: Option<usize>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub hmm_end: Option<usize>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub hmm_length: Option<usize>,
+    /// "COMPLETE", "N_TERMINAL_COMPLETE", "C_TERMINAL_COMPLETE" or
+    /// "INCOMPLETE"
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub hmm_bounds: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub envelope_start: Option<usize>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub envelope_end: Option<usize>,
+    /// True if InterProScan chose this location for its set of
+    /// non-overlapping domains for the protein
+    #[serde(default, skip_serializing_if="std::ops::Not::not")]
+    pub representative: bool,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub fragments: Vec<LocationFragment>,
+}
+
+/// A GO term mapped to an InterPro entry (InterPro2GO)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GoXref {
+    /// eg. "GO:0005515"
+    pub id: String,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub name: Option<String>,
+    /// "BIOLOGICAL_PROCESS", "MOLECULAR_FUNCTION" or "CELLULAR_COMPONENT"
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// A pathway (eg. from Reactome or MetaCyc) linked to an InterPro entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PathwayXref {
+    pub database: String,
+    pub id: String,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InterProMatch {
     pub id: String,
     pub dbname: String,
+    /// The member database release, eg. "37.0" for Pfam
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub dbversion: Option<String>,
     #[serde(skip_serializing_if="Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if="Option::is_none")]
@@ -51,27 +124,195 @@ pub struct InterProMatch {
     pub interpro_name: Option<String>,
     #[serde(skip_serializing_if="Option::is_none")]
     pub interpro_description: Option<String>,
+    /// The InterPro entry type, eg. "Family", "Domain", "Repeat" or "Site"
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub interpro_type: Option<String>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub go_xrefs: Vec<GoXref>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub pathway_xrefs: Vec<PathwayXref>,
+    /// The score and E-value of the whole match, for analyses that report
+    /// them
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub evalue: Option<f64>,
     pub match_start: usize,
     pub match_end: usize,
     pub locations: Vec<Location>,
+    /// The match locations with their scores and fragments, sorted by start
+    /// position.  `locations` has the merged fragments of these locations.
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub match_locations: Vec<MatchLocation>,
+}
+
+/// A member database signature that contributes to an InterPro entry
+/// level match
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EntrySignature {
+    pub dbname: String,
+    pub id: String,
 }
 
-#[derive(Serialize, Debug, Clone)]
+/// The matches of all the signatures of one InterPro entry combined, with
+/// the union of their locations
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterProEntryMatch {
+    pub interpro_id: String,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub interpro_name: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub interpro_description: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub interpro_type: Option<String>,
+    pub signatures: Vec<EntrySignature>,
+    pub match_start: usize,
+    pub match_end: usize,
+    pub locations: Vec<Location>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TMMatch {
     pub start: usize,
     pub end: usize,
 }
 
-#[derive(Serialize, Debug, Clone)]
+/// The TMHMM prediction for a residue range
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TMRegion {
+    /// Cytoplasmic
+    #[serde(rename = "inside")]
+    Inside,
+    /// Extracellular or lumenal
+    #[serde(rename = "outside")]
+    Outside,
+    #[serde(rename = "TMhelix")]
+    TMHelix,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TMSegment {
+    pub region: TMRegion,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The full TMHMM prediction for a protein from the long output format
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TMHMMTopology {
+    /// "Number of predicted TMHs"
+    pub helix_count: usize,
+    /// "Exp number of AAs in TMHs" - more than 18 suggests a TM protein
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub expected_helix_residues: Option<f64>,
+    /// "Exp number, first 60 AAs" - more than 10 suggests that a predicted
+    /// helix near the N-terminus may be a signal peptide
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub expected_first_60_residues: Option<f64>,
+    /// "Total prob of N-in"
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub n_in_probability: Option<f64>,
+    /// True if TMHMM warned "POSSIBLE N-term signal sequence"
+    #[serde(default, skip_serializing_if="std::ops::Not::not")]
+    pub possible_signal_sequence: bool,
+    /// The inside, outside and TMhelix segments, covering the whole protein
+    pub segments: Vec<TMSegment>,
+}
+
+impl TMHMMTopology {
+    /// The TMhelix segments
+    pub fn helices(&self) -> Vec<TMMatch> {
+        self.segments.iter()
+            .filter(|segment| segment.region == TMRegion::TMHelix)
+            .map(|segment| TMMatch {
+                start: segment.start,
+                end: segment.end,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GeneMatches {
     pub gene_uniquename: String,
+    /// The lower case MD5 of the protein sequence from the InterProScan
+    /// results, if available
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub sequence_md5: Option<String>,
     pub interpro_matches: Vec<InterProMatch>,
+    /// The `interpro_matches` combined by InterPro entry
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub interpro_entries: Vec<InterProEntryMatch>,
+    /// The low complexity regions from segmasker or SEG.  Unlike the other
+    /// features these have zero-based coordinates, as segmasker reports
+    /// them.  Use [`GeneMatches::one_based_segmasker_matches`] to compare
+    /// them with the other features or the protein length.
     pub segmasker_matches: Vec<Location>,
     pub tmhmm_matches: Vec<TMMatch>,
+    /// The TMHMM topology, for proteins with at least one predicted helix
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub tmhmm_topology: Option<TMHMMTopology>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DomainData {
     pub interproscan_version: String,
+    /// The program that found the `segmasker_matches` of the genes:
+    /// "segmasker", or "seg" for the built-in SEG implementation.  Older
+    /// results files don't have it and are from segmasker.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub low_complexity_method: Option<String>,
     pub domains_by_id: BTreeMap<String, GeneMatches>,
 }
+
+impl GeneMatches {
+    /// Create a GeneMatches with no features
+    pub fn new(gene_uniquename: String) -> GeneMatches {
+        GeneMatches {
+            gene_uniquename,
+            sequence_md5: None,
+            interpro_matches: vec![],
+            interpro_entries: vec![],
+            segmasker_matches: vec![],
+            tmhmm_matches: vec![],
+            tmhmm_topology: None,
+        }
+    }
+
+    /// Return the `segmasker_matches` with one-based coordinates
+    pub fn one_based_segmasker_matches(&self) -> impl Iterator<Item = Location> + '_ {
+        self.segmasker_matches.iter()
+            .map(|location| Location {
+                start: location.start + 1,
+                end: location.end + 1,
+            })
+    }
+}
+
+impl DomainData {
+    /// Read a domain results file written by [`DomainData::write`].  The file
+    /// may be gzip or zstd compressed.
+    pub fn load(filename: &str) -> Result<DomainData> {
+        let reader = open_input(filename)?;
+
+        serde_json::from_reader(reader)
+            .map_err(|source| DomainProcessError::Json {
+                filename: filename.into(),
+                source,
+            })
+    }
+
+    /// Write the domain results as JSON, compressing if the file name ends
+    /// with ".gz" or ".zst".
+    pub fn write(&self, filename: &str) -> Result<()> {
+        let mut writer = OutputWriter::create(filename)?;
+
+        serde_json::to_writer(&mut writer, self)
+            .map_err(|source| DomainProcessError::Json {
+                filename: filename.into(),
+                source,
+            })?;
+
+        writer.finish()
+    }
+}
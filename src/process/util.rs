@@ -1,3 +1,11 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::error::{DomainProcessError, Result};
 use crate::types::Location;
 
 // merge locations/ranges that abut or overlap
@@ -24,3 +32,104 @@ pub fn merge_locations(locations: &mut Vec<Location>)
         }
     }
 }
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+fn open_input_helper(filename: &str) -> io::Result<Box<dyn BufRead + Send>>
+{
+    let mut reader = BufReader::new(File::open(filename)?);
+
+    let start = reader.fill_buf()?;
+
+    if start.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else if start.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Open a file for reading, decompressing it if it starts with the gzip or
+/// zstd magic bytes.
+pub fn open_input(filename: &str) -> Result<Box<dyn BufRead + Send>>
+{
+    open_input_helper(filename)
+        .map_err(|err| DomainProcessError::io(filename, err))
+}
+
+/// Return the file name without a trailing ".gz" or ".zst"
+pub fn strip_compression_extension(filename: &str) -> &str {
+    filename.strip_suffix(".gz")
+        .or_else(|| filename.strip_suffix(".zst"))
+        .unwrap_or(filename)
+}
+
+enum CompressedWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+/// A file writer that compresses based on the file name extension:
+/// ".gz" for gzip and ".zst" for zstd.  `finish()` must be called to
+/// write the compression trailer and to see any errors.
+pub struct OutputWriter {
+    filename: String,
+    writer: CompressedWriter,
+}
+
+impl OutputWriter {
+    pub fn create(filename: &str) -> Result<OutputWriter> {
+        let make_writer = || {
+            let writer = BufWriter::new(File::create(filename)?);
+
+            if filename.ends_with(".gz") {
+                Ok(CompressedWriter::Gzip(GzEncoder::new(writer, Compression::default())))
+            } else if filename.ends_with(".zst") {
+                Ok(CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?))
+            } else {
+                Ok(CompressedWriter::Plain(writer))
+            }
+        };
+
+        Ok(OutputWriter {
+            filename: filename.into(),
+            writer: make_writer().map_err(|err| DomainProcessError::io(filename, err))?,
+        })
+    }
+
+    pub fn finish(self) -> Result<()> {
+        let OutputWriter { filename, writer } = self;
+
+        let finish_helper = || {
+            let mut writer = match writer {
+                CompressedWriter::Plain(writer) => writer,
+                CompressedWriter::Gzip(encoder) => encoder.finish()?,
+                CompressedWriter::Zstd(encoder) => encoder.finish()?,
+            };
+            writer.flush()
+        };
+
+        finish_helper().map_err(|err| DomainProcessError::io(&filename, err))
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.writer {
+            CompressedWriter::Plain(ref mut writer) => writer.write(buf),
+            CompressedWriter::Gzip(ref mut encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(ref mut encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer {
+            CompressedWriter::Plain(ref mut writer) => writer.flush(),
+            CompressedWriter::Gzip(ref mut encoder) => encoder.flush(),
+            CompressedWriter::Zstd(ref mut encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::fasta::Protein;
+use crate::id_mapping::IdMapper;
+use crate::types::{DomainData, GeneMatches};
+
+/// A feature with coordinates outside the protein sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfRangeFeature {
+    pub gene_uniquename: String,
+    /// For InterPro matches the database name and match ID (eg. "Pfam
+    /// PF04847"), otherwise "TMHMM" or "segmasker"
+    pub feature: String,
+    /// One-based coordinates
+    pub start: usize,
+    pub end: usize,
+    pub protein_length: usize,
+}
+
+/// The result of checking the feature coordinates
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// The number of genes with a protein sequence in the FASTA file
+    pub genes_checked: usize,
+    /// Genes that have features but no protein in the FASTA file
+    pub genes_without_protein: Vec<String>,
+    pub out_of_range: Vec<OutOfRangeFeature>,
+}
+
+impl ValidationReport {
+    /// The number of genes with at least one out of range feature
+    pub fn out_of_range_gene_count(&self) -> usize {
+        let mut gene_count = 0;
+        let mut prev_gene = None;
+
+        // out_of_range is ordered by gene
+        for feature in &self.out_of_range {
+            if prev_gene != Some(&feature.gene_uniquename) {
+                gene_count += 1;
+                prev_gene = Some(&feature.gene_uniquename);
+            }
+        }
+
+        gene_count
+    }
+}
+
+/// Return a map from gene ID to protein length.  If a gene has several
+/// proteins the longest is used.
+pub fn protein_lengths(proteins: &[Protein], id_mapper: &IdMapper) -> HashMap<String, usize> {
+    let mut lengths = HashMap::new();
+
+    for protein in proteins {
+        let length = lengths.entry(id_mapper.gene_id(&protein.id)).or_insert(0);
+        *length = protein.len().max(*length);
+    }
+
+    lengths
+}
+
+fn in_range(start: usize, end: usize, protein_length: usize) -> bool {
+    start >= 1 && start <= end && end <= protein_length
+}
+
+fn check_gene(gene_matches: &mut GeneMatches, protein_length: usize, drop_invalid: bool,
+              out_of_range: &mut Vec<OutOfRangeFeature>)
+{
+    let gene_uniquename = &gene_matches.gene_uniquename;
+
+    let mut add_feature = |feature: String, start: usize, end: usize| {
+        out_of_range.push(OutOfRangeFeature {
+            gene_uniquename: gene_uniquename.clone(),
+            feature,
+            start,
+            end,
+            protein_length,
+        });
+    };
+
+    // a match is dropped if any of its locations is out of range
+    gene_matches.interpro_matches.retain(|interpro_match| {
+        let mut valid = true;
+
+        for location in &interpro_match.locations {
+            if !in_range(location.start, location.end, protein_length) {
+                add_feature(format!("{} {}", interpro_match.dbname, interpro_match.id),
+                            location.start, location.end);
+                valid = false;
+            }
+        }
+
+        valid || !drop_invalid
+    });
+
+    gene_matches.tmhmm_matches.retain(|tm_match| {
+        let valid = in_range(tm_match.start, tm_match.end, protein_length);
+        if !valid {
+            add_feature("TMHMM".into(), tm_match.start, tm_match.end);
+        }
+        valid || !drop_invalid
+    });
+
+    // out of range helices are reported above - the topology is removed
+    // along with them
+    if drop_invalid && let Some(ref topology) = gene_matches.tmhmm_topology &&
+        topology.segments.iter()
+            .any(|segment| !in_range(segment.start, segment.end, protein_length))
+    {
+        gene_matches.tmhmm_topology = None;
+    }
+
+    let segmasker_valid: Vec<_> = gene_matches.one_based_segmasker_matches()
+        .map(|location| {
+            let valid = in_range(location.start, location.end, protein_length);
+            if !valid {
+                add_feature("segmasker".into(), location.start, location.end);
+            }
+            valid
+        })
+        .collect();
+
+    let mut segmasker_valid = segmasker_valid.into_iter();
+    gene_matches.segmasker_matches
+        .retain(|_| segmasker_valid.next().unwrap_or(true) || !drop_invalid);
+}
+
+/// Check that the coordinates of all features are within the protein
+/// sequence of their gene.  If `drop_invalid` is true, out of range
+/// features are removed from `domain_data`.  Genes without a protein length
+/// aren't checked.
+pub fn validate_coordinates(domain_data: &mut DomainData,
+                            protein_lengths: &HashMap<String, usize>,
+                            drop_invalid: bool)
+    -> ValidationReport
+{
+    let mut report = ValidationReport::default();
+
+    for (gene_uniquename, gene_matches) in domain_data.domains_by_id.iter_mut() {
+        match protein_lengths.get(gene_uniquename) {
+            Some(&protein_length) => {
+                report.genes_checked += 1;
+                check_gene(gene_matches, protein_length, drop_invalid,
+                           &mut report.out_of_range);
+            },
+            None => report.genes_without_protein.push(gene_uniquename.clone()),
+        }
+    }
+
+    report
+}
+
+/// Differences between the InterProScan results and the peptide FASTA file
+#[derive(Debug, Default)]
+pub struct SequenceCheckReport {
+    /// Genes where the MD5 in the InterProScan results doesn't match the
+    /// sequence in the FASTA file
+    pub changed: Vec<String>,
+    /// Genes in the FASTA file that have no InterProScan result
+    pub missing_from_results: Vec<String>,
+    /// Genes with InterProScan results but no protein in the FASTA file
+    pub not_in_fasta: Vec<String>,
+}
+
+impl SequenceCheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.changed.is_empty() && self.missing_from_results.is_empty() &&
+            self.not_in_fasta.is_empty()
+    }
+}
+
+/// Compare the sequence MD5s of the InterProScan results with the proteins
+/// in the FASTA file.  Genes without a `sequence_md5` (eg. those with only
+/// TMHMM or segmasker features) are treated as having no result.  If a gene
+/// has several proteins, any of their MD5s can match.  If `check_missing` is
+/// false, genes without a result aren't reported.  This is needed for TSV
+/// input, which has no rows for proteins without matches.
+pub fn check_sequences(domain_data: &DomainData, proteins: &[Protein], id_mapper: &IdMapper,
+                       check_missing: bool)
+    -> SequenceCheckReport
+{
+    let mut fasta_md5s: HashMap<String, BTreeSet<String>> = HashMap::new();
+
+    for protein in proteins {
+        fasta_md5s.entry(id_mapper.gene_id(&protein.id))
+            .or_default()
+            .insert(protein.md5());
+    }
+
+    let mut report = SequenceCheckReport::default();
+
+    for (gene_uniquename, gene_matches) in &domain_data.domains_by_id {
+        let Some(ref result_md5) = gene_matches.sequence_md5
+        else {
+            continue;
+        };
+
+        match fasta_md5s.get(gene_uniquename) {
+            Some(md5s) => {
+                if !md5s.contains(&result_md5.to_ascii_lowercase()) {
+                    report.changed.push(gene_uniquename.clone());
+                }
+            },
+            None => report.not_in_fasta.push(gene_uniquename.clone()),
+        }
+    }
+
+    if !check_missing {
+        return report;
+    }
+
+    report.missing_from_results = fasta_md5s.into_keys()
+        .filter(|gene_uniquename| {
+            domain_data.domains_by_id.get(gene_uniquename)
+                .is_none_or(|gene_matches| gene_matches.sequence_md5.is_none())
+        })
+        .collect();
+    report.missing_from_results.sort();
+
+    report
+}
@@ -1,19 +1,61 @@
 extern crate domain_process;
 
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Write};
 
+use domain_process::error::DomainProcessError;
+use domain_process::id_mapping::IdMapper;
 use domain_process::interpro_parse;
+use domain_process::interpro_xml;
 use domain_process::segmasker;
-use domain_process::util::merge_locations;
-use domain_process::types::Location;
+use domain_process::tmhmm;
+use domain_process::seg::{self, SegParameters};
+use domain_process::fasta::{self, Protein};
+use domain_process::util::{merge_locations, OutputWriter};
+use domain_process::gff3;
+use domain_process::validate;
+use domain_process::incremental;
+use domain_process::diff::{self, ChangeType};
+use domain_process::stats;
+use domain_process::gaf;
+use domain_process::filter::{self, MatchThresholds};
+use domain_process::entries;
+use domain_process::architecture::{self, ArchitectureSource};
+use domain_process::types::{DomainData, EntrySignature, GeneMatches, GoXref, InterProMatch,
+                            Location, LocationFragment, TMMatch, TMRegion, TMSegment};
+
+fn protein(id: &str, sequence: &str) -> Protein {
+    Protein {
+        id: id.into(),
+        description: None,
+        sequence: sequence.into(),
+    }
+}
+
+// Parse an InterProScan results file in any of the input formats
+fn parse_domain_data(filename: &str) -> DomainData {
+    let (interproscan_version, domains_by_id) =
+        interpro_parse::parse_with_format(filename,
+                                          interpro_parse::detect_input_format(filename).unwrap(),
+                                          &IdMapper::default()).unwrap();
+    DomainData {
+        interproscan_version,
+        low_complexity_method: None,
+        domains_by_id,
+    }
+}
+
+fn small_domain_data() -> DomainData {
+    parse_domain_data("tests/small_matches.json")
+}
 
 #[test]
 fn test_parse() {
 
-    let (interproscan_version, matches) = interpro_parse::parse("tests/small_matches.json");
+    let (interproscan_version, matches) = interpro_parse::parse("tests/small_matches.json", &IdMapper::default()).unwrap();
 
-    assert_eq!(interproscan_version, "v109.0 (InterProScan v6.0.1)");
+    assert_eq!(interproscan_version, "v109.0 (InterProScan v6.0.1, IPS6 JSON)");
 
     let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
     assert_eq!(spac13g6_15c.gene_uniquename, "SPAC13G6.15c");
@@ -47,7 +89,7 @@ fn test_parse() {
 fn test_parse_segmasker() {
     let file = File::open("tests/small_segmasker_output.txt").unwrap();
     let mut reader = BufReader::new(file);
-    let results = segmasker::parse(&mut reader);
+    let results = segmasker::parse(&mut reader, &IdMapper::default()).unwrap();
 
     let spac1250_07 = results.get("SPAC1250.07").unwrap();
     assert_eq!(spac1250_07.len(), 2);
@@ -56,6 +98,32 @@ fn test_parse_segmasker() {
     assert_eq!(second_loc.end, 155);
 }
 
+#[test]
+fn test_parse_errors() {
+    let mut reader = BufReader::new(">SPAC1250.07.1:pep sfc7\n19 - 35\n139 to 155\n".as_bytes());
+    match segmasker::parse(&mut reader, &IdMapper::default()) {
+        Err(DomainProcessError::MalformedSegmaskerLine { line_number, line }) => {
+            assert_eq!(line_number, 3);
+            assert_eq!(line, "139 to 155");
+        },
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    match interpro_parse::parse("tests/no_such_file.json", &IdMapper::default()) {
+        Err(err @ DomainProcessError::Io { .. }) => {
+            assert!(err.to_string().contains("tests/no_such_file.json"));
+        },
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    match interpro_parse::parse("tests/small_segmasker_output.txt", &IdMapper::default()) {
+        Err(DomainProcessError::Json { filename, .. }) => {
+            assert_eq!(filename, "tests/small_segmasker_output.txt");
+        },
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
 #[test]
 fn test_segmasker_location_merge() {
     let mut locations = vec![
@@ -128,3 +196,833 @@ fn test_segmasker_location_merge_2() {
 
     assert_eq!(locations.len(), 2);
 }
+
+#[test]
+fn test_parse_tsv() {
+    assert_eq!(interpro_parse::detect_input_format("tests/small_matches.tsv").unwrap(),
+               interpro_parse::InputFormat::Tsv);
+
+    let (interproscan_version, matches) = interpro_parse::parse_tsv("tests/small_matches.tsv", &IdMapper::default()).unwrap();
+
+    assert_eq!(interproscan_version, "unknown (InterProScan TSV output)");
+
+    let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+    assert_eq!(spac13g6_15c.interpro_matches.len(), 3);
+
+    let pfam_match = spac13g6_15c.interpro_matches.first().unwrap();
+    assert_eq!(pfam_match.dbname, "Pfam");
+    assert_eq!(pfam_match.id, "PF04847");
+    assert_eq!(pfam_match.interpro_id.as_deref(), Some("IPR006931"));
+    // the TSV format has the entry description but not its short name
+    assert_eq!(pfam_match.interpro_name, None);
+    assert_eq!(pfam_match.interpro_description.as_deref(), Some("Calcipressin"));
+
+    let mobidb_match = spac13g6_15c.interpro_matches.get(1).unwrap();
+    assert_eq!(mobidb_match.dbname, "MobiDB-Disorder");
+    assert_eq!(mobidb_match.id, "mobidb-lite-Disorder");
+
+    let spac1250_07 = matches.get("SPAC1250.07").unwrap();
+    let repeat_match = spac1250_07.interpro_matches.first().unwrap();
+    assert_eq!(repeat_match.match_start, 20);
+    assert_eq!(repeat_match.match_end, 90);
+    assert_eq!(repeat_match.locations.len(), 1);
+}
+
+#[test]
+fn test_parse_xml() {
+    assert_eq!(interpro_parse::detect_input_format("tests/small_matches.xml").unwrap(),
+               interpro_parse::InputFormat::Xml);
+
+    let (interproscan_version, matches) = interpro_xml::parse_xml("tests/small_matches.xml", &IdMapper::default()).unwrap();
+
+    assert_eq!(interproscan_version, "v91.0 (InterProScan v5.59-91.0)");
+
+    let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+    assert_eq!(spac13g6_15c.interpro_matches.len(), 2);
+
+    let pfam_match = spac13g6_15c.interpro_matches.first().unwrap();
+    assert_eq!(pfam_match.id, "PF04847");
+    assert_eq!(pfam_match.name.as_deref(), Some("Calcipressin"));
+    assert_eq!(pfam_match.interpro_id.as_deref(), Some("IPR006931"));
+    assert_eq!(pfam_match.match_start, 6);
+    assert_eq!(pfam_match.match_end, 143);
+    assert_eq!(pfam_match.locations.len(), 2);
+    assert_eq!(pfam_match.locations[1].start, 80);
+
+    let panther_match = spac13g6_15c.interpro_matches.get(1).unwrap();
+    assert_eq!(panther_match.id, "PTHR10300");
+    assert_eq!(panther_match.name, None);
+    assert_eq!(panther_match.locations[0].end, 156);
+}
+
+#[test]
+fn test_parse_ips5_json() {
+    let (interproscan_version, matches) = interpro_parse::parse("tests/small_matches_ips5.json", &IdMapper::default()).unwrap();
+
+    assert_eq!(interproscan_version, "v91.0 (InterProScan v5.59-91.0, IPS5 JSON)");
+
+    let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+    assert_eq!(spac13g6_15c.interpro_matches.len(), 6);
+
+    let dbnames: Vec<_> = spac13g6_15c.interpro_matches.iter()
+        .map(|interpro_match| interpro_match.dbname.as_str())
+        .collect();
+    assert_eq!(dbnames, vec!["Pfam", "CATH-Gene3D", "MobiDB-Disorder", "MobiDB-Polar", "PANTHER",
+                         "PROSITE profiles"]);
+}
+
+#[test]
+fn test_parse_streaming() {
+    let mut gene_uniquenames = vec![];
+
+    let interproscan_version =
+        interpro_parse::parse_streaming("tests/small_matches.json", &IdMapper::default(),
+                                        |gene_matches| {
+            assert_eq!(gene_matches.interpro_matches.len(), 4);
+            gene_uniquenames.push(gene_matches.gene_uniquename);
+        }).unwrap();
+
+    assert_eq!(interproscan_version, "v109.0 (InterProScan v6.0.1, IPS6 JSON)");
+    assert_eq!(gene_uniquenames, vec!["SPAC13G6.15c"]);
+}
+
+#[test]
+fn test_compressed_input() {
+    let json = std::fs::read("tests/small_matches.json").unwrap();
+
+    for extension in ["gz", "zst"] {
+        let compressed_filename =
+            std::env::temp_dir().join(format!("domain_process_small_matches.json.{}", extension));
+        let compressed_filename = compressed_filename.to_str().unwrap();
+
+        let mut writer = OutputWriter::create(compressed_filename).unwrap();
+        writer.write_all(&json).unwrap();
+        writer.finish().unwrap();
+
+        assert_ne!(std::fs::read(compressed_filename).unwrap(), json);
+
+        assert_eq!(interpro_parse::detect_input_format(compressed_filename).unwrap(),
+                   interpro_parse::InputFormat::Json);
+
+        let (_, matches) = interpro_parse::parse(compressed_filename, &IdMapper::default()).unwrap();
+        let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+        assert_eq!(spac13g6_15c.interpro_matches.len(), 4);
+
+        std::fs::remove_file(compressed_filename).unwrap();
+    }
+}
+
+#[test]
+fn test_domain_data_load() {
+    let domain_data = small_domain_data();
+
+    let results_filename = std::env::temp_dir().join("domain_process_domain_results.json.gz");
+    let results_filename = results_filename.to_str().unwrap();
+
+    domain_data.write(results_filename).unwrap();
+
+    let loaded_domain_data = DomainData::load(results_filename).unwrap();
+
+    assert_eq!(loaded_domain_data.interproscan_version, domain_data.interproscan_version);
+
+    let spac13g6_15c = loaded_domain_data.domains_by_id.get("SPAC13G6.15c").unwrap();
+    assert_eq!(spac13g6_15c.interpro_matches.len(), 4);
+
+    let panther_match = spac13g6_15c.interpro_matches.get(3).unwrap();
+    assert_eq!(panther_match.id, "PTHR10300");
+    assert_eq!(panther_match.name, None);
+    assert_eq!(panther_match.interpro_id.as_deref(), Some("IPR006931"));
+    assert_eq!(panther_match.locations, vec![Location { start: 7, end: 156 }]);
+
+    std::fs::remove_file(results_filename).unwrap();
+}
+
+#[test]
+fn test_id_mapping() {
+    let default_mapper = IdMapper::default();
+    assert_eq!(default_mapper.gene_id("SPAC1250.07.1:pep"), "SPAC1250.07");
+    assert_eq!(default_mapper.gene_id("SPAC1250.07"), "SPAC1250.07");
+
+    let japonicus_mapper = IdMapper::from_pattern(r"^(SJAG_\d+)T\d+$").unwrap();
+    assert_eq!(japonicus_mapper.gene_id("SJAG_01234T0"), "SJAG_01234");
+
+    assert!(IdMapper::from_pattern(r"^SJAG_\d+").is_err());
+
+    let file_mapper = IdMapper::from_mapping_file("tests/small_id_mapping.txt").unwrap();
+    assert_eq!(file_mapper.gene_id("SPAC1250.05.1:pep"), "rpl3002");
+
+    let file = File::open("tests/small_segmasker_output.txt").unwrap();
+    let mut reader = BufReader::new(file);
+    let results = segmasker::parse(&mut reader, &file_mapper).unwrap();
+
+    assert_eq!(results.get("sfc7").unwrap().len(), 2);
+    assert_eq!(results.get("rpl3002").unwrap().len(), 1);
+}
+
+#[test]
+fn test_write_gff3() {
+    let mut domain_data = small_domain_data();
+
+    let spac13g6_15c = domain_data.domains_by_id.get_mut("SPAC13G6.15c").unwrap();
+    spac13g6_15c.tmhmm_matches.push(TMMatch { start: 20, end: 42 });
+    spac13g6_15c.segmasker_matches.push(Location { start: 0, end: 12 });
+
+    let mut output = vec![];
+    gff3::write_gff3(&domain_data, &mut output).unwrap();
+    let gff3_string = String::from_utf8(output).unwrap();
+    let lines: Vec<_> = gff3_string.lines().collect();
+
+    assert_eq!(lines[0], "##gff-version 3");
+    assert_eq!(lines[2],
+               "SPAC13G6.15c\tPfam\tprotein_match\t6\t143\t.\t.\t.\t\
+                ID=SPAC13G6.15c:PF04847;Name=PF04847;Note=Calcipressin;Dbxref=InterPro:IPR006931;\
+                Ontology_term=GO:0019722,GO:0008597");
+    assert_eq!(lines[6],
+               "SPAC13G6.15c\tTMHMM\ttransmembrane_polypeptide_region\t20\t42\t.\t.\t.\t.");
+    assert_eq!(lines[7],
+               "SPAC13G6.15c\tsegmasker\tcompositionally_biased_region_of_peptide\t1\t13\t.\t.\t.\t.");
+
+    domain_data.low_complexity_method = Some("seg".into());
+    let mut output = vec![];
+    gff3::write_gff3(&domain_data, &mut output).unwrap();
+    let gff3_string = String::from_utf8(output).unwrap();
+    assert!(gff3_string.contains("SPAC13G6.15c\tseg\tcompositionally_biased_region_of_peptide\t1\t13\t"));
+}
+
+#[test]
+fn test_seg() {
+    let proteins = fasta::read("tests/small.fa").unwrap();
+    let results = seg::run(&proteins, &SegParameters::default(), &IdMapper::default());
+
+    // same as the segmasker output
+    let spac1250_05 = results.get("SPAC1250.05").unwrap();
+    assert_eq!(spac1250_05, &vec![Location { start: 0, end: 12 }]);
+
+    let spac1250_07 = results.get("SPAC1250.07").unwrap();
+    assert_eq!(spac1250_07, &vec![Location { start: 19, end: 35 },
+                                  Location { start: 139, end: 155 }]);
+
+    assert!(!results.contains_key("SPAC1250.03"));
+    assert!(!results.contains_key("SPAC1250.04c"));
+
+    assert!(seg::seg_sequence(b"MSSS", &SegParameters::default()).is_empty());
+}
+
+#[test]
+fn test_fasta() {
+    let proteins = fasta::read("tests/small.fa").unwrap();
+    assert_eq!(proteins.len(), 4);
+    assert_eq!(proteins[0].id, "SPAC1250.03.1:pep");
+    assert_eq!(proteins[0].description.as_deref(),
+               Some("ubc14|ubiquitin conjugating enzyme E2 for HECT-type and RBR family E3 Ub ligases, Ubc14"));
+    assert!(proteins.iter().all(|protein| !protein.sequence.contains('*')));
+
+    let input = ">prot1 a description\nMAK*L\nSTV*\n>prot2\nMK**\n";
+    let proteins = fasta::parse(&mut input.as_bytes(), "test input").unwrap();
+    assert_eq!(proteins, vec![
+        Protein {
+            id: "prot1".into(),
+            description: Some("a description".into()),
+            sequence: "MAKXLSTV".into(),
+        },
+        Protein {
+            id: "prot2".into(),
+            description: None,
+            sequence: "MK".into(),
+        },
+    ]);
+
+    let mut output = vec![];
+    fasta::write(&proteins, &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(),
+               ">prot1 a description\nMAKXLSTV\n>prot2\nMK\n");
+
+    let err = fasta::parse(&mut "MAK\n".as_bytes(), "test input").unwrap_err();
+    assert!(err.to_string().contains("line 1"));
+}
+
+#[test]
+fn test_validate_coordinates() {
+    let mut domain_data = small_domain_data();
+
+    let spac13g6_15c = domain_data.domains_by_id.get_mut("SPAC13G6.15c").unwrap();
+    spac13g6_15c.tmhmm_matches.push(TMMatch { start: 20, end: 42 });
+    spac13g6_15c.tmhmm_matches.push(TMMatch { start: 140, end: 160 });
+    spac13g6_15c.segmasker_matches.push(Location { start: 0, end: 12 });
+    spac13g6_15c.segmasker_matches.push(Location { start: 145, end: 150 });
+
+    let mut protein_lengths = HashMap::new();
+    protein_lengths.insert("SPAC13G6.15c".to_owned(), 150);
+
+    let report = validate::validate_coordinates(&mut domain_data, &protein_lengths, false);
+    assert_eq!(report.genes_checked, 1);
+    assert!(report.genes_without_protein.is_empty());
+    assert_eq!(report.out_of_range_gene_count(), 1);
+
+    let features: Vec<_> = report.out_of_range.iter()
+        .map(|feature| (feature.feature.as_str(), feature.start, feature.end))
+        .collect();
+    assert!(features.contains(&("PANTHER PTHR10300", 7, 156)));
+    assert!(features.contains(&("TMHMM", 140, 160)));
+    assert!(features.contains(&("segmasker", 146, 151)));
+    assert!(!features.iter().any(|(feature, _, _)| *feature == "Pfam PF04847"));
+
+    // nothing is removed unless requested
+    let spac13g6_15c = domain_data.domains_by_id.get("SPAC13G6.15c").unwrap();
+    assert_eq!(spac13g6_15c.tmhmm_matches.len(), 2);
+
+    let report = validate::validate_coordinates(&mut domain_data, &protein_lengths, true);
+    assert_eq!(report.out_of_range.len(), features.len());
+
+    let spac13g6_15c = domain_data.domains_by_id.get("SPAC13G6.15c").unwrap();
+    let ids: Vec<_> = spac13g6_15c.interpro_matches.iter()
+        .map(|interpro_match| interpro_match.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["PF04847"]);
+    assert_eq!(spac13g6_15c.tmhmm_matches.len(), 1);
+    assert_eq!(spac13g6_15c.segmasker_matches, vec![Location { start: 0, end: 12 }]);
+
+    let report = validate::validate_coordinates(&mut domain_data, &HashMap::new(), false);
+    assert_eq!(report.genes_without_protein, vec!["SPAC13G6.15c".to_owned()]);
+}
+
+const SPAC13G6_15C_SEQUENCE: &str =
+    "MLVFTTSPDHVDELNEFVQQLNPVAFTRVLRGLGKVLASYNDKAVEEDTLKKSSTGSLPSGQQVHCQYVLDDPNHVEGISVDQSLQVPKFEKNWLISPPGSPPVGWEPIVEESPNSQHLAHDIQLKLDELGNALLNDHSAGPQIVISEHNNTKETSPSRQFEH";
+
+#[test]
+fn test_check_sequences() {
+    for filename in ["tests/small_matches.json", "tests/small_matches.tsv", "tests/small_matches.xml"] {
+        let format = interpro_parse::detect_input_format(filename).unwrap();
+        let (_, matches) =
+            interpro_parse::parse_with_format(filename, format, &IdMapper::default()).unwrap();
+        let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+        assert_eq!(spac13g6_15c.sequence_md5.as_deref(), Some("3e384f1f8cb0c23464a589559bd6892c"));
+    }
+
+    let domain_data = small_domain_data();
+
+    let proteins = vec![protein("SPAC13G6.15c.1:pep", SPAC13G6_15C_SEQUENCE)];
+    assert_eq!(proteins[0].md5(), "3e384f1f8cb0c23464a589559bd6892c");
+    let report = validate::check_sequences(&domain_data, &proteins, &IdMapper::default(), true);
+    assert!(report.is_ok());
+
+    let proteins = vec![
+        protein("SPAC13G6.15c.1:pep", &SPAC13G6_15C_SEQUENCE[1..]),
+        protein("SPAC1250.05.1:pep", "MSK"),
+    ];
+    let report = validate::check_sequences(&domain_data, &proteins, &IdMapper::default(), true);
+    assert_eq!(report.changed, vec!["SPAC13G6.15c".to_owned()]);
+    assert_eq!(report.missing_from_results, vec!["SPAC1250.05".to_owned()]);
+    assert!(report.not_in_fasta.is_empty());
+
+    // TSV input has no rows for proteins without matches
+    let report = validate::check_sequences(&domain_data, &proteins, &IdMapper::default(), false);
+    assert_eq!(report.changed, vec!["SPAC13G6.15c".to_owned()]);
+    assert!(report.missing_from_results.is_empty());
+
+    let report = validate::check_sequences(&domain_data, &[], &IdMapper::default(), true);
+    assert_eq!(report.not_in_fasta, vec!["SPAC13G6.15c".to_owned()]);
+}
+
+#[test]
+fn test_incremental() {
+    let mut previous = small_domain_data();
+    previous.domains_by_id.get_mut("SPAC13G6.15c").unwrap()
+        .tmhmm_matches.push(TMMatch { start: 20, end: 42 });
+
+    let proteins = vec![
+        protein("SPAC13G6.15c.1:pep", SPAC13G6_15C_SEQUENCE),
+        protein("SPAC1250.05.1:pep", "MSK"),
+    ];
+
+    let changed = incremental::changed_proteins(&previous, &proteins, &IdMapper::default());
+    let changed_ids: Vec<_> = changed.iter().map(|protein| protein.id.as_str()).collect();
+    assert_eq!(changed_ids, vec!["SPAC1250.05.1:pep"]);
+
+    let mut domains_by_id = BTreeMap::new();
+    let copied_count = incremental::merge_previous(previous.clone(), &mut domains_by_id,
+                                                   &proteins, &IdMapper::default());
+    assert_eq!(copied_count, 1);
+    let spac13g6_15c = domains_by_id.get("SPAC13G6.15c").unwrap();
+    assert_eq!(spac13g6_15c.interpro_matches.len(), 4);
+    // TMHMM and low complexity features are recalculated
+    assert!(spac13g6_15c.tmhmm_matches.is_empty());
+
+    // changed proteins aren't copied
+    let proteins = vec![protein("SPAC13G6.15c.1:pep", &SPAC13G6_15C_SEQUENCE[1..])];
+    let changed = incremental::changed_proteins(&previous, &proteins, &IdMapper::default());
+    assert_eq!(changed.len(), 1);
+    let mut domains_by_id = BTreeMap::new();
+    let copied_count = incremental::merge_previous(previous, &mut domains_by_id,
+                                                   &proteins, &IdMapper::default());
+    assert_eq!(copied_count, 0);
+    assert!(domains_by_id.is_empty());
+}
+
+#[test]
+fn test_diff() {
+    let old = small_domain_data();
+
+    let mut new = old.clone();
+    let spac13g6_15c = new.domains_by_id.get_mut("SPAC13G6.15c").unwrap();
+    spac13g6_15c.interpro_matches.retain(|interpro_match| interpro_match.dbname != "PANTHER");
+    let pfam_match = spac13g6_15c.interpro_matches.iter_mut()
+        .find(|interpro_match| interpro_match.dbname == "Pfam")
+        .unwrap();
+    pfam_match.locations = vec![Location { start: 8, end: 143 }];
+    pfam_match.dbversion = Some("39.0".into());
+    spac13g6_15c.tmhmm_matches.push(TMMatch { start: 20, end: 42 });
+
+    let domain_data_diff = diff::diff(&old, &new);
+
+    assert!(domain_data_diff.genes_added.is_empty());
+    // PANTHER has no matches in the new results
+    let version_changes: Vec<_> = domain_data_diff.database_versions.iter()
+        .map(|version_change| (version_change.dbname.as_str(),
+                               version_change.old_version.as_deref(),
+                               version_change.new_version.as_deref()))
+        .collect();
+    assert_eq!(version_changes, vec![("PANTHER", Some("19.0"), None),
+                                     ("Pfam", Some("38.2"), Some("39.0"))]);
+
+    let changes: Vec<_> = domain_data_diff.changes.iter()
+        .map(|change| (change.dbname.as_str(), change.id.as_str(), change.change))
+        .collect();
+    assert_eq!(changes, vec![("PANTHER", "PTHR10300", ChangeType::Removed),
+                             ("Pfam", "PF04847", ChangeType::Changed),
+                             ("TMHMM", "", ChangeType::Added)]);
+    assert_eq!(domain_data_diff.changes[1].new_locations.as_deref(), Some("8..143"));
+    assert_eq!(domain_data_diff.by_dbname.get("TMHMM").unwrap().added, 1);
+
+    let mut output = vec![];
+    diff::write_tsv(&domain_data_diff, &mut output).unwrap();
+    let tsv = String::from_utf8(output).unwrap();
+    assert_eq!(tsv.lines().nth(3).unwrap(), "SPAC13G6.15c\tTMHMM\t\tadded\t\t20..42");
+
+    let mut output = vec![];
+    diff::write_text(&domain_data_diff, &mut output).unwrap();
+    let text = String::from_utf8(output).unwrap();
+    assert!(text.contains("  Pfam: 38.2 -> 39.0\n"));
+    assert!(text.contains("    ~ Pfam PF04847 6..143 -> 8..143\n"));
+}
+
+#[test]
+fn test_stats() {
+    let mut domain_data = small_domain_data();
+    let spac13g6_15c = domain_data.domains_by_id.get_mut("SPAC13G6.15c").unwrap();
+    spac13g6_15c.tmhmm_matches.push(TMMatch { start: 20, end: 42 });
+    spac13g6_15c.tmhmm_matches.push(TMMatch { start: 60, end: 82 });
+    spac13g6_15c.segmasker_matches.push(Location { start: 0, end: 9 });
+    spac13g6_15c.segmasker_matches.push(Location { start: 5, end: 14 });
+
+    let domain_stats = stats::stats(&domain_data, None, &IdMapper::default());
+    assert_eq!(domain_stats.gene_count, 1);
+    assert_eq!(domain_stats.genes_with_interpro_match, 1);
+    assert_eq!(domain_stats.distinct_interpro_ids, 1);
+    assert_eq!(domain_stats.by_dbname.get("Pfam").unwrap().match_count, 1);
+    assert_eq!(domain_stats.genes_with_tm_helices, 1);
+    assert_eq!(domain_stats.tm_helix_count_distribution.get(&2), Some(&1));
+    assert_eq!(domain_stats.low_complexity_residues, 15);
+    // MobiDB regions 142..163 and 153..163
+    assert_eq!(domain_stats.disorder_residues, 22);
+    assert_eq!(domain_stats.low_complexity_fraction, None);
+
+    let proteins = fasta::read("tests/small.fa").unwrap();
+    let domain_stats = stats::stats(&domain_data, Some(&proteins), &IdMapper::default());
+    assert_eq!(domain_stats.gene_count, 5);
+    assert_eq!(domain_stats.genes_without_features, 4);
+    let total_residues: usize = proteins.iter().map(|protein| protein.len()).sum();
+    assert_eq!(domain_stats.total_residues, Some(total_residues));
+    assert_eq!(domain_stats.low_complexity_fraction, Some(15.0 / total_residues as f64));
+
+    let mut output = vec![];
+    stats::write_tsv(&domain_stats, &mut output).unwrap();
+    let tsv = String::from_utf8(output).unwrap();
+    assert!(tsv.contains("summary\tgenes_with_interpro_match\t1\n"));
+    assert!(tsv.contains("dbname_matches\tPANTHER\t1\n"));
+    assert!(tsv.contains("tm_helix_count_genes\t2\t1\n"));
+}
+
+#[test]
+fn test_interpro_entry_details() {
+    for filename in ["tests/small_matches.json", "tests/small_matches.tsv", "tests/small_matches.xml"] {
+        let format = interpro_parse::detect_input_format(filename).unwrap();
+        let (_, matches) =
+            interpro_parse::parse_with_format(filename, format, &IdMapper::default()).unwrap();
+        let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+        let pfam_match = spac13g6_15c.interpro_matches.iter()
+            .find(|interpro_match| interpro_match.id == "PF04847")
+            .unwrap();
+
+        let go_ids: Vec<_> = pfam_match.go_xrefs.iter().map(|go_xref| go_xref.id.as_str()).collect();
+        assert_eq!(go_ids, vec!["GO:0019722", "GO:0008597"]);
+
+        assert_eq!(pfam_match.pathway_xrefs.len(), 1);
+        assert_eq!(pfam_match.pathway_xrefs[0].database, "Reactome");
+        assert_eq!(pfam_match.pathway_xrefs[0].id, "R-HSA-2025928");
+
+        if format == interpro_parse::InputFormat::Tsv {
+            // the TSV format has no entry types or GO term details
+            assert_eq!(pfam_match.interpro_type, None);
+            assert_eq!(pfam_match.go_xrefs[0].category, None);
+        } else {
+            assert_eq!(pfam_match.interpro_type.as_deref(), Some("Family"));
+            assert_eq!(pfam_match.go_xrefs[0].category.as_deref(), Some("BIOLOGICAL_PROCESS"));
+            assert_eq!(pfam_match.go_xrefs[1].name.as_deref(),
+                       Some("calcium-dependent protein serine/threonine phosphatase regulator activity"));
+        }
+    }
+}
+
+#[test]
+fn test_write_gaf() {
+    let domain_data = small_domain_data();
+
+    let mut options = gaf::GafOptions {
+        db: "PomBase".into(),
+        taxon: 4896,
+        date: "20250612".into(),
+        blacklist: Default::default(),
+        complex_terms: Default::default(),
+    };
+
+    let mut output = vec![];
+    let annotation_count = gaf::write_gaf(&domain_data, &options, &mut output).unwrap();
+    assert_eq!(annotation_count, 2);
+    let gaf_string = String::from_utf8(output).unwrap();
+    let lines: Vec<_> = gaf_string.lines().collect();
+    assert_eq!(lines[0], "!gaf-version: 2.2");
+    assert!(lines.contains(&"!date-generated: 2025-06-12"));
+    assert_eq!(lines[lines.len() - 2],
+               "PomBase\tSPAC13G6.15c\tSPAC13G6.15c\tenables\tGO:0008597\tGO_REF:0000002\tIEA\t\
+                InterPro:IPR006931\tF\t\t\tprotein\ttaxon:4896\t20250612\tInterPro\t\t");
+    assert_eq!(lines[lines.len() - 1].split('\t').count(), 17);
+
+    options.blacklist.insert("GO:0008597".into());
+    let mut output = vec![];
+    assert_eq!(gaf::write_gaf(&domain_data, &options, &mut output).unwrap(), 1);
+    let gaf_string = String::from_utf8(output).unwrap();
+    assert!(gaf_string.contains("\tinvolved_in\tGO:0019722\t"));
+    assert!(!gaf_string.contains("GO:0008597"));
+
+    // complexes are annotated with part_of, other components with located_in
+    let mut domain_data = domain_data;
+    let pfam_match = domain_data.domains_by_id.get_mut("SPAC13G6.15c").unwrap()
+        .interpro_matches.iter_mut()
+        .find(|interpro_match| interpro_match.id == "PF04847")
+        .unwrap();
+    for go_id in ["GO:0005737", "GO:0005955"] {
+        pfam_match.go_xrefs.push(GoXref {
+            id: go_id.into(),
+            name: None,
+            category: Some("CELLULAR_COMPONENT".into()),
+        });
+    }
+    options.complex_terms.insert("GO:0005955".into());
+    let mut output = vec![];
+    gaf::write_gaf(&domain_data, &options, &mut output).unwrap();
+    let gaf_string = String::from_utf8(output).unwrap();
+    assert!(gaf_string.contains("\tlocated_in\tGO:0005737\t"));
+    assert!(gaf_string.contains("\tpart_of\tGO:0005955\t"));
+
+    assert_eq!(gaf::today().len(), 8);
+}
+
+#[test]
+fn test_match_scores() {
+    for filename in ["tests/small_matches.json", "tests/small_matches.xml"] {
+        let mut domain_data = parse_domain_data(filename);
+
+        let spac13g6_15c = domain_data.domains_by_id.get("SPAC13G6.15c").unwrap();
+        let pfam_match = spac13g6_15c.interpro_matches.iter()
+            .find(|interpro_match| interpro_match.id == "PF04847")
+            .unwrap();
+
+        assert_eq!(pfam_match.score, Some(65.3));
+        assert_eq!(pfam_match.evalue, Some(2.0E-14));
+        assert_eq!(pfam_match.match_locations.len(), 1);
+
+        let location = &pfam_match.match_locations[0];
+        assert_eq!((location.start, location.end), (6, 143));
+        assert_eq!(location.score, Some(65.0));
+        assert_eq!(location.evalue, Some(2.4E-14));
+        assert_eq!((location.hmm_start, location.hmm_end, location.hmm_length),
+                   (Some(5), Some(131), Some(186)));
+        assert_eq!(location.hmm_bounds.as_deref(), Some("INCOMPLETE"));
+        assert_eq!((location.envelope_start, location.envelope_end), (Some(2), Some(162)));
+
+        let mut thresholds = MatchThresholds::default();
+        thresholds.set_max_evalue("pfam", 1e-15);
+        thresholds.set_min_score("PANTHER", 50.0);
+        assert_eq!(thresholds.apply(&mut domain_data), 1);
+
+        let dbnames: Vec<_> = domain_data.domains_by_id.get("SPAC13G6.15c").unwrap()
+            .interpro_matches.iter()
+            .map(|interpro_match| interpro_match.dbname.as_str())
+            .collect();
+        assert!(!dbnames.contains(&"Pfam"));
+        assert!(dbnames.contains(&"PANTHER"));
+    }
+
+    // TSV input has the E-value, or the score for some analyses, of each
+    // location
+    let mut domain_data = parse_domain_data("tests/small_matches_ips5.tsv");
+    let tsv_location = |domain_data: &DomainData, id: &str| {
+        domain_data.domains_by_id.get("SPAC13G6.15c").unwrap()
+            .interpro_matches.iter()
+            .find(|interpro_match| interpro_match.id == id)
+            .map(|interpro_match| interpro_match.match_locations[0].clone())
+    };
+    let pfam_location = tsv_location(&domain_data, "PF04847").unwrap();
+    assert_eq!((pfam_location.score, pfam_location.evalue), (None, Some(2.0E-14)));
+    let prosite_location = tsv_location(&domain_data, "PS50102").unwrap();
+    assert_eq!((prosite_location.score, prosite_location.evalue), (Some(9.5), None));
+
+    let mut thresholds = MatchThresholds::default();
+    thresholds.set_max_evalue("Pfam", 1e-15);
+    thresholds.set_min_score("PROSITE profiles", 10.0);
+    assert_eq!(thresholds.apply(&mut domain_data), 2);
+    assert!(tsv_location(&domain_data, "PF04847").is_none());
+
+    assert_eq!(filter::parse_threshold("Pfam=1e-5"), Some(("Pfam".into(), 1e-5)));
+    assert_eq!(filter::parse_threshold("Pfam"), None);
+    assert_eq!(filter::parse_threshold("=1"), None);
+}
+
+#[test]
+fn test_location_fragments() {
+    let (_, matches) =
+        interpro_xml::parse_xml("tests/small_matches.xml", &IdMapper::default()).unwrap();
+    let spac13g6_15c = matches.get("SPAC13G6.15c").unwrap();
+    let pfam_match = spac13g6_15c.interpro_matches.iter()
+        .find(|interpro_match| interpro_match.id == "PF04847")
+        .unwrap();
+
+    // the flat locations are still the fragments
+    assert_eq!(pfam_match.locations,
+               vec![Location { start: 6, end: 60 }, Location { start: 80, end: 143 }]);
+
+    // but the fragments stay grouped in their location
+    assert_eq!(pfam_match.match_locations.len(), 1);
+    assert_eq!(pfam_match.match_locations[0].fragments,
+               vec![LocationFragment { start: 6, end: 60, dc_status: Some("C_TERMINAL_DISC".into()) },
+                    LocationFragment { start: 80, end: 143, dc_status: Some("N_TERMINAL_DISC".into()) }]);
+
+    let json = serde_json::to_string(pfam_match).unwrap();
+    let from_json: InterProMatch = serde_json::from_str(&json).unwrap();
+    assert_eq!(from_json.match_locations, pfam_match.match_locations);
+}
+
+#[test]
+fn test_representative_only() {
+    for (filename, representative_dbname) in [("tests/small_matches.json", "PANTHER"),
+                                              ("tests/small_matches.xml", "Pfam")] {
+        let mut domain_data = parse_domain_data(filename);
+
+        assert!(filter::has_representative_locations(&domain_data));
+
+        let removed_count = filter::representative_only(&mut domain_data);
+        assert_eq!(removed_count, 1);
+
+        let representative_matches: Vec<_> =
+            domain_data.domains_by_id.get("SPAC13G6.15c").unwrap()
+            .interpro_matches.iter()
+            .filter(|interpro_match| filter::can_be_representative(&interpro_match.dbname))
+            .collect();
+        assert_eq!(representative_matches.len(), 1);
+        assert_eq!(representative_matches[0].dbname, representative_dbname);
+        assert!(representative_matches[0].match_locations[0].representative);
+    }
+
+    let domain_data = parse_domain_data("tests/small_matches.tsv");
+    assert!(!filter::has_representative_locations(&domain_data));
+}
+
+#[test]
+fn test_representative_only_keeps_go_and_disorder() {
+    let mut domain_data = small_domain_data();
+    entries::add_entry_matches(&mut domain_data);
+
+    let options = gaf::GafOptions {
+        db: "PomBase".into(),
+        taxon: 4896,
+        date: "20250612".into(),
+        blacklist: Default::default(),
+        complex_terms: Default::default(),
+    };
+    let write_gaf = |domain_data: &DomainData| {
+        let mut output = vec![];
+        gaf::write_gaf(domain_data, &options, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    // the GAF is written before the representative filter because the GO
+    // terms of IPR006931 are only on the Pfam match, which isn't
+    // representative
+    let gaf = write_gaf(&domain_data);
+    assert_eq!(gaf.lines().filter(|line| !line.starts_with('!')).count(), 2);
+
+    let entry_signatures = |domain_data: &DomainData| {
+        domain_data.domains_by_id.get("SPAC13G6.15c").unwrap()
+            .interpro_entries.iter()
+            .map(|entry_match| (entry_match.interpro_id.clone(), entry_match.signatures.clone()))
+            .collect::<Vec<_>>()
+    };
+    let entry_signatures_before = entry_signatures(&domain_data);
+
+    filter::representative_only(&mut domain_data);
+
+    let spac13g6_15c = domain_data.domains_by_id.get("SPAC13G6.15c").unwrap();
+    assert!(spac13g6_15c.interpro_matches.iter()
+            .any(|interpro_match| interpro_match.dbname == "MobiDB-Disorder"));
+    // the entry still lists the Pfam signature
+    assert_eq!(entry_signatures(&domain_data), entry_signatures_before);
+    assert_eq!(entry_signatures_before[0].1.len(), 2);
+
+    let domain_stats = stats::stats(&domain_data, None, &IdMapper::default());
+    assert_eq!(domain_stats.disorder_residues, 22);
+}
+
+#[test]
+fn test_entry_matches() {
+    let mut domain_data = small_domain_data();
+    let spac13g6_15c = domain_data.domains_by_id.get("SPAC13G6.15c").unwrap();
+
+    let entry_matches = entries::entry_matches(&spac13g6_15c.interpro_matches);
+
+    // the PANTHER and Pfam matches are both in IPR006931 and the MobiDB
+    // matches aren't in an entry
+    assert_eq!(entry_matches.len(), 1);
+    let entry_match = &entry_matches[0];
+    assert_eq!(entry_match.interpro_id, "IPR006931");
+    assert_eq!(entry_match.interpro_type.as_deref(), Some("Family"));
+    assert_eq!(entry_match.signatures,
+               vec![EntrySignature { dbname: "PANTHER".into(), id: "PTHR10300".into() },
+                    EntrySignature { dbname: "Pfam".into(), id: "PF04847".into() }]);
+    assert_eq!(entry_match.locations, vec![Location { start: 6, end: 156 }]);
+    assert_eq!((entry_match.match_start, entry_match.match_end), (6, 156));
+
+    entries::add_entry_matches(&mut domain_data);
+    assert_eq!(domain_data.domains_by_id.get("SPAC13G6.15c").unwrap().interpro_entries.len(), 1);
+}
+
+#[test]
+fn test_architecture() {
+    let domain_data = parse_domain_data("tests/small_matches.tsv");
+
+    let pfam_architectures = architecture::architectures(&domain_data, ArchitectureSource::Pfam);
+    assert_eq!(pfam_architectures.get("SPAC13G6.15c").map(String::as_str), Some("PF04847"));
+    // the two PF12345 locations are merged in the flat locations but each
+    // is a domain in the architecture
+    assert_eq!(pfam_architectures.get("SPAC1250.07").map(String::as_str),
+               Some("PF12345~PF12345"));
+
+    // no entry types in TSV input so the IPR006931 family is included
+    let interpro_architectures =
+        architecture::architectures(&domain_data, ArchitectureSource::InterPro);
+    assert_eq!(interpro_architectures.len(), 1);
+    assert_eq!(interpro_architectures.get("SPAC13G6.15c").map(String::as_str), Some("IPR006931"));
+
+    assert!(architecture::architectures(&domain_data, ArchitectureSource::Representative)
+            .is_empty());
+
+    let mut architectures = BTreeMap::new();
+    architectures.insert("SPAC1.01".to_owned(), "PF00069~PF00433".to_owned());
+    architectures.insert("SPAC2.02".to_owned(), "PF00069".to_owned());
+    architectures.insert("SPBC3.03".to_owned(), "PF00069~PF00433".to_owned());
+    let groups = architecture::group_by_architecture(&architectures);
+
+    let mut output = vec![];
+    architecture::write_groups_tsv(&groups, &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(),
+               "architecture\tgene_count\tgenes\n\
+                PF00069~PF00433\t2\tSPAC1.01,SPBC3.03\n\
+                PF00069\t1\tSPAC2.02\n");
+
+    // families aren't part of the InterPro architecture
+    let domain_data = small_domain_data();
+    assert!(architecture::architectures(&domain_data, ArchitectureSource::InterPro).is_empty());
+    assert_eq!(architecture::architectures(&domain_data, ArchitectureSource::Representative)
+               .get("SPAC13G6.15c").map(String::as_str), Some("PTHR10300"));
+}
+
+#[test]
+fn test_parse_tmhmm() {
+    let file = File::open("tests/small_tmhmm_output.txt").unwrap();
+    let mut reader = BufReader::new(file);
+    let topologies = tmhmm::parse(&mut reader, "small_tmhmm_output.txt").unwrap();
+
+    // proteins without a helix aren't included
+    assert_eq!(topologies.len(), 2);
+
+    let topology = topologies.get("SPAC1250.07.1:pep").unwrap();
+    assert_eq!(topology.helix_count, 2);
+    assert_eq!(topology.expected_helix_residues, Some(44.61237));
+    assert_eq!(topology.expected_first_60_residues, Some(21.98104));
+    assert_eq!(topology.n_in_probability, Some(0.84353));
+    assert!(topology.possible_signal_sequence);
+    assert_eq!(topology.segments.len(), 5);
+    assert_eq!(topology.segments[0], TMSegment { region: TMRegion::Inside, start: 1, end: 11 });
+    assert_eq!(topology.segments[2], TMSegment { region: TMRegion::Outside, start: 35, end: 120 });
+
+    let helices: Vec<_> = topology.helices().iter()
+        .map(|helix| (helix.start, helix.end))
+        .collect();
+    assert_eq!(helices, vec![(12, 34), (121, 143)]);
+
+    let json = serde_json::to_string(&topology.segments[1]).unwrap();
+    assert_eq!(json, r#"{"region":"TMhelix","start":12,"end":34}"#);
+
+    // the helices and topology of a gene come from its longest protein
+    let id_mapper = IdMapper::from_pattern(r"^(\S+?)\.\d+:pep$").unwrap();
+    let proteins = vec![protein("SPAC1250.07.1:pep", &"M".repeat(240)),
+                        protein("SPAC1250.07.2:pep", &"M".repeat(120))];
+    let by_gene = tmhmm::topologies_by_gene(topologies.clone(), &proteins, &id_mapper);
+    assert_eq!(by_gene.len(), 1);
+    assert_eq!(by_gene.get("SPAC1250.07").unwrap().helix_count, 2);
+
+    let proteins = vec![protein("SPAC1250.07.1:pep", &"M".repeat(100)),
+                        protein("SPAC1250.07.2:pep", &"M".repeat(120))];
+    let by_gene = tmhmm::topologies_by_gene(topologies, &proteins, &id_mapper);
+    assert_eq!(by_gene.get("SPAC1250.07").unwrap().helix_count, 1);
+
+    let bad_output = "SPAC1250.07.1:pep\tTMHMM2.0\tloop\t1\t11\n";
+    let result = tmhmm::parse(&mut bad_output.as_bytes(), "bad");
+    assert!(matches!(result, Err(DomainProcessError::MalformedInput { line_number: Some(1), .. })));
+
+    for helix_count in ["1.7", "-1"] {
+        let bad_output = format!("# SPAC1250.07.1:pep Number of predicted TMHs:  {}\n", helix_count);
+        let result = tmhmm::parse(&mut bad_output.as_bytes(), "bad");
+        assert!(matches!(result, Err(DomainProcessError::MalformedInput { line_number: Some(1), .. })));
+    }
+
+    // values that aren't in the output aren't invented
+    let short_output = "SPAC1250.07.1:pep\tTMHMM2.0\tTMhelix\t1\t20\n";
+    let topologies = tmhmm::parse(&mut short_output.as_bytes(), "short").unwrap();
+    let topology = topologies.get("SPAC1250.07.1:pep").unwrap();
+    assert_eq!(topology.helix_count, 1);
+    assert_eq!(topology.expected_helix_residues, None);
+    assert_eq!(topology.expected_first_60_residues, None);
+}
+
+#[test]
+fn test_tsv_dbnames() {
+    let dbnames = |matches: &BTreeMap<String, GeneMatches>| {
+        matches.get("SPAC13G6.15c").unwrap().interpro_matches.iter()
+            .map(|interpro_match| interpro_match.dbname.clone())
+            .collect::<BTreeSet<_>>()
+    };
+
+    let (_, json_matches) =
+        interpro_parse::parse("tests/small_matches_ips5.json", &IdMapper::default()).unwrap();
+    let (_, tsv_matches) =
+        interpro_parse::parse_tsv("tests/small_matches_ips5.tsv", &IdMapper::default()).unwrap();
+
+    let mut json_dbnames = dbnames(&json_matches);
+    // the TSV format has no sequence features
+    json_dbnames.remove("MobiDB-Polar");
+
+    assert_eq!(dbnames(&tsv_matches), json_dbnames);
+    assert!(json_dbnames.contains("PROSITE profiles"));
+    assert!(json_dbnames.contains("CATH-Gene3D"));
+}